@@ -0,0 +1,183 @@
+use crate::checkpoint::{put_f64, put_u32, Reader};
+use crate::math::{luminance, Color};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// `--noise-threshold` settings: each pixel takes at least `min_spp` samples,
+/// then more in batches of `min_spp` until its relative error drops below
+/// `threshold` or it reaches `max_spp`.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveOpts {
+    pub threshold: f64,
+    pub min_spp: u32,
+    pub max_spp: u32,
+}
+
+impl AdaptiveOpts {
+    /// Whether a pixel that has taken `stats.count()` samples is done.
+    pub fn converged(&self, stats: &PixelStats) -> bool {
+        let n = stats.count();
+        if n >= self.max_spp {
+            return true;
+        }
+        // Only test at batch boundaries so samplers see whole batches
+        n >= self.min_spp
+            && (n - self.min_spp).is_multiple_of(self.min_spp.max(1))
+            && stats.relative_error() < self.threshold
+    }
+}
+
+/// Running mean and variance of sample luminance (Welford's algorithm).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PixelStats {
+    n: u32,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStats {
+    pub fn add(&mut self, c: Color) {
+        let x = luminance(c);
+        self.n += 1;
+        let d = x - self.mean;
+        self.mean += d / self.n as f64;
+        self.m2 += d * (x - self.mean);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.n
+    }
+
+    /// Variance of the mean luminance; infinite with fewer than two samples.
+    pub fn mean_variance(&self) -> f64 {
+        if self.n < 2 {
+            return f64::INFINITY;
+        }
+        self.m2 / (self.n - 1) as f64 / self.n as f64
+    }
+
+    pub const SAVED_BYTES: usize = 20;
+
+    pub fn save(&self, out: &mut Vec<u8>) {
+        put_u32(out, self.n);
+        put_f64(out, self.mean);
+        put_f64(out, self.m2);
+    }
+
+    pub fn load(r: &mut Reader) -> Result<Self, String> {
+        Ok(Self {
+            n: r.u32()?,
+            mean: r.f64()?,
+            m2: r.f64()?,
+        })
+    }
+
+    /// Standard error of the mean relative to the square root of the mean,
+    /// which roughly matches how visible noise is after gamma encoding: bright
+    /// pixels tolerate more absolute noise than dark ones.
+    pub fn relative_error(&self) -> f64 {
+        self.mean_variance().sqrt() / self.mean.max(1e-4).sqrt()
+    }
+}
+
+// Black -> blue -> red -> yellow -> white
+pub fn heat_color(t: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.1, 0.1, 0.8],
+        [0.9, 0.1, 0.1],
+        [1.0, 0.9, 0.1],
+        [1.0, 1.0, 1.0],
+    ];
+    let x = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let i = (x as usize).min(STOPS.len() - 2);
+    let f = x - i as f64;
+    let c = |k: usize| ((STOPS[i][k] * (1.0 - f) + STOPS[i + 1][k] * f) * 255.0).round() as u8;
+    [c(0), c(1), c(2)]
+}
+
+/// Writes the per-pixel sample counts as a false-color PPM, from black
+/// (`min_spp`) to white (`max_spp`). `counts` rows run bottom to top.
+pub fn write_heatmap(
+    path: &str,
+    width: usize,
+    height: usize,
+    counts: &[u32],
+    min_spp: u32,
+    max_spp: u32,
+) -> std::io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "P3")?;
+    writeln!(w, "{width} {height}")?;
+    writeln!(w, "255")?;
+    let span = (max_spp - min_spp).max(1) as f64;
+    for j in (0..height).rev() {
+        for i in 0..width {
+            let t = (counts[j * width + i].saturating_sub(min_spp)) as f64 / span;
+            let [r, g, b] = heat_color(t);
+            writeln!(w, "{r} {g} {b}")?;
+        }
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(values: &[f64]) -> PixelStats {
+        let mut s = PixelStats::default();
+        for &v in values {
+            s.add(Color::new(v, v, v));
+        }
+        s
+    }
+
+    #[test]
+    fn running_variance_matches_two_pass() {
+        let values = [0.3, 1.7, 0.2, 0.9, 2.5, 0.4, 1.1];
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let s = stats(&values);
+        assert_eq!(s.count(), 7);
+        let expected = (var / n).sqrt() / mean.sqrt();
+        assert!((s.relative_error() - expected).abs() < 1e-12);
+        assert!(stats(&[0.5]).relative_error().is_infinite());
+
+        let mut out = Vec::new();
+        s.save(&mut out);
+        assert_eq!(out.len(), PixelStats::SAVED_BYTES);
+        let back = PixelStats::load(&mut Reader::new(&out)).unwrap();
+        assert_eq!(back.count(), s.count());
+        assert_eq!(back.relative_error(), s.relative_error());
+    }
+
+    #[test]
+    fn pixels_converge_at_batch_boundaries_once_quiet() {
+        let opts = AdaptiveOpts {
+            threshold: 0.05,
+            min_spp: 4,
+            max_spp: 32,
+        };
+        // A flat pixel stops after the first batch, not before
+        assert!(!opts.converged(&stats(&[0.5; 3])));
+        assert!(opts.converged(&stats(&[0.5; 4])));
+        // A noisy one keeps going until max_spp
+        let noisy: Vec<f64> = (0..32).map(|i| (i % 2) as f64).collect();
+        for n in 4..32 {
+            assert!(!opts.converged(&stats(&noisy[..n])), "stopped at {n}");
+        }
+        assert!(opts.converged(&stats(&noisy)));
+        // Quiet enough, but only checked at a multiple of the batch size
+        let mut mostly_flat = vec![0.5; 4];
+        mostly_flat[0] = 0.9;
+        mostly_flat.extend([0.5; 40]);
+        let first = (4..=32).find(|&n| opts.converged(&stats(&mostly_flat[..n])));
+        assert!(first.is_some_and(|n| n > 4 && n % 4 == 0), "{first:?}");
+        // The same absolute noise counts for less in a bright pixel
+        let bright: Vec<f64> = (0..8).map(|i| 4.0 + 0.2 * (i % 2) as f64).collect();
+        let dark: Vec<f64> = bright.iter().map(|v| v - 3.75).collect();
+        assert!(stats(&bright).relative_error() < stats(&dark).relative_error());
+    }
+}
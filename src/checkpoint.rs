@@ -0,0 +1,73 @@
+use std::io::Write;
+
+// File layout (little-endian): magic, version, then whatever the render state
+// writes with the put_* helpers.
+pub const MAGIC: &[u8; 4] = b"RTCK";
+pub const VERSION: u32 = 1;
+
+pub fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+pub fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+// Stored bit for bit, so a resumed render continues from exactly the same sums
+pub fn put_f64(out: &mut Vec<u8>, v: f64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+/// Cursor over checkpoint bytes; every read fails cleanly on truncated data.
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        if self.data.len() < N {
+            return Err("checkpoint is truncated".into());
+        }
+        let (head, rest) = self.data.split_at(N);
+        self.data = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, String> {
+        self.bytes().map(f64::from_le_bytes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// 64-bit FNV-1a; stable across builds, unlike `std`'s default hasher.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Writes `path.tmp` and renames it over `path`, so a crash mid-write leaves
+/// the previous checkpoint intact.
+pub fn write_atomic(path: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = format!("{path}.tmp");
+    let mut f = std::fs::File::create(&tmp)?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    drop(f);
+    std::fs::rename(&tmp, path)
+}
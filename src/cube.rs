@@ -1,7 +1,10 @@
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, Shape};
+use crate::material::Material;
 use crate::math::{Color, Point3, Vec3};
 use crate::ray::Ray;
+use crate::stats::{self, Prim};
 
+#[derive(Clone, Copy, Debug)]
 pub struct Aabb {
     pub min: Point3,
     pub max: Point3,
@@ -12,9 +15,11 @@ impl Aabb {
         Self { min, max }
     }
 
-    // Slab test: returns (tmin, face_normal) for the first hit within [t_min, t_max]
+    // Slab test: returns (t, outward face normal) for the first hit within
+    // [t_min, t_max]. A ray that starts inside hits where it leaves.
     fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, Vec3)> {
         let mut face_normal = Vec3::new(0.0, 0.0, 0.0);
+        let mut exit_face = Vec3::new(0.0, 0.0, 0.0);
 
         // For each axis, update interval and record which face we entered from
         for axis in 0..3 {
@@ -57,11 +62,13 @@ impl Aabb {
             let mut t0 = (minb - origin) * inv;
             let mut t1 = (maxb - origin) * inv;
             let mut enter_normal = normal_neg;
-            let _exit_normal = normal_pos;
+            let mut exit_normal = normal_pos;
 
             if t0 > t1 {
                 std::mem::swap(&mut t0, &mut t1);
-                enter_normal = -enter_normal; // flipped because we swapped
+                // flipped because we swapped
+                enter_normal = -enter_normal;
+                exit_normal = -exit_normal;
             }
 
             if t0 > t_min {
@@ -70,6 +77,7 @@ impl Aabb {
             }
             if t1 < t_max {
                 t_max = t1;
+                exit_face = exit_normal;
             }
 
             if t_max <= t_min {
@@ -77,43 +85,287 @@ impl Aabb {
             }
         }
 
-        Some((t_min, face_normal))
+        if face_normal.length_squared() > 0.0 {
+            Some((t_min, face_normal))
+        } else if exit_face.length_squared() > 0.0 {
+            // Entered before t_min: inside the box
+            Some((t_max, exit_face))
+        } else {
+            // Inside, and still inside at t_max
+            None
+        }
     }
 }
 
 pub struct Cube {
     pub bounds: Aabb,
-    pub albedo: Color,
-    pub reflectivity: f64,
+    pub material: Material,
 }
 
 impl Cube {
     /// Construct from center and edge size
     pub fn from_center_size(center: Point3, size: f64, albedo: Color, reflectivity: f64) -> Self {
+        Self::with_material(center, size, Material::new(albedo, reflectivity))
+    }
+
+    pub fn with_material(center: Point3, size: f64, material: Material) -> Self {
         let h = size * 0.5;
         let min = Point3::new(center.x - h, center.y - h, center.z - h);
         let max = Point3::new(center.x + h, center.y + h, center.z + h);
         Self {
             bounds: Aabb::new(min, max),
-            albedo,
-            reflectivity,
+            material,
+        }
+    }
+
+    // Each face maps to the full [0,1]^2 square, oriented as seen from outside
+    // with +Y up (the top and bottom faces have -Z up).
+    fn face_uv(&self, p: Point3, n: Vec3) -> (f64, f64, Vec3, Vec3) {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        let e = max - min;
+        let l = p - min;
+        if n.x > 0.5 {
+            let u = 1.0 - l.z / e.z;
+            (
+                u,
+                l.y / e.y,
+                Vec3::new(0.0, 0.0, -e.z),
+                Vec3::new(0.0, e.y, 0.0),
+            )
+        } else if n.x < -0.5 {
+            (
+                l.z / e.z,
+                l.y / e.y,
+                Vec3::new(0.0, 0.0, e.z),
+                Vec3::new(0.0, e.y, 0.0),
+            )
+        } else if n.y > 0.5 {
+            let v = 1.0 - l.z / e.z;
+            (
+                l.x / e.x,
+                v,
+                Vec3::new(e.x, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, -e.z),
+            )
+        } else if n.y < -0.5 {
+            (
+                l.x / e.x,
+                l.z / e.z,
+                Vec3::new(e.x, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, e.z),
+            )
+        } else if n.z > 0.5 {
+            (
+                l.x / e.x,
+                l.y / e.y,
+                Vec3::new(e.x, 0.0, 0.0),
+                Vec3::new(0.0, e.y, 0.0),
+            )
+        } else {
+            let u = 1.0 - l.x / e.x;
+            (
+                u,
+                l.y / e.y,
+                Vec3::new(-e.x, 0.0, 0.0),
+                Vec3::new(0.0, e.y, 0.0),
+            )
         }
     }
 }
 
 impl Hittable for Cube {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_tests(Prim::Cube, 1);
         if let Some((t, outward_normal)) = self.bounds.hit(r, t_min, t_max) {
             let p = r.at(t);
-            return Some(HitRecord::with_face_normal(
-                r,
-                p,
-                outward_normal,
-                t,
-                self.albedo,
-                self.reflectivity,
-            ));
+            let (u, v, dpdu, dpdv) = self.face_uv(p, outward_normal);
+            return Some(
+                HitRecord::with_face_normal(r, p, outward_normal, t, &self.material)
+                    .with_uv(u, v, dpdu, dpdv),
+            );
         }
         None
     }
 }
+
+impl Shape for Cube {
+    fn area(&self) -> f64 {
+        let d = self.bounds.max - self.bounds.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    fn sample_surface(&self, u: (f64, f64)) -> (Point3, Vec3) {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        let d = max - min;
+        // Pick one of the six faces proportionally to its area, then reuse
+        // the remainder of u.0 as the first coordinate on that face.
+        let faces = [
+            d.y * d.z,
+            d.y * d.z,
+            d.x * d.z,
+            d.x * d.z,
+            d.x * d.y,
+            d.x * d.y,
+        ];
+        let total: f64 = faces.iter().sum();
+        let mut pick = u.0 * total;
+        let mut face = 5;
+        for (i, a) in faces.iter().enumerate() {
+            if pick < *a {
+                face = i;
+                break;
+            }
+            pick -= a;
+        }
+        let s = (pick / faces[face]).clamp(0.0, 1.0);
+        let t = u.1;
+        match face {
+            0 => (
+                Point3::new(min.x, min.y + s * d.y, min.z + t * d.z),
+                Vec3::new(-1.0, 0.0, 0.0),
+            ),
+            1 => (
+                Point3::new(max.x, min.y + s * d.y, min.z + t * d.z),
+                Vec3::new(1.0, 0.0, 0.0),
+            ),
+            2 => (
+                Point3::new(min.x + s * d.x, min.y, min.z + t * d.z),
+                Vec3::new(0.0, -1.0, 0.0),
+            ),
+            3 => (
+                Point3::new(min.x + s * d.x, max.y, min.z + t * d.z),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+            4 => (
+                Point3::new(min.x + s * d.x, min.y + t * d.y, min.z),
+                Vec3::new(0.0, 0.0, -1.0),
+            ),
+            _ => (
+                Point3::new(min.x + s * d.x, min.y + t * d.y, max.z),
+                Vec3::new(0.0, 0.0, 1.0),
+            ),
+        }
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::testing::{check_hit, direction, outward, point};
+    use proptest::prelude::*;
+
+    // Any box, not just cubes: center and half extents
+    fn boxes() -> impl Strategy<Value = Aabb> {
+        (point(2.0), 0.05..2.0, 0.05..2.0, 0.05..2.0).prop_map(|(c, hx, hy, hz)| {
+            let h = Vec3::new(hx, hy, hz);
+            Aabb::new(c - h, c + h)
+        })
+    }
+
+    fn cube(bounds: Aabb) -> Cube {
+        Cube {
+            bounds,
+            material: Material::new(Color::new(0.5, 0.5, 0.5), 0.0),
+        }
+    }
+
+    // Directions with some components exactly zero, which take the slab
+    // test's parallel branch
+    fn axis_aligned_direction() -> impl Strategy<Value = Vec3> {
+        (direction(), 1..7u8).prop_map(|(d, keep)| {
+            let pick = |bit: u8, v: f64| if keep & bit != 0 { v } else { 0.0 };
+            Vec3::new(pick(1, d.x), pick(2, d.y), pick(4, d.z))
+        })
+    }
+
+    fn any_direction() -> impl Strategy<Value = Vec3> {
+        prop_oneof![direction(), axis_aligned_direction()]
+    }
+
+    fn axis(v: Vec3, k: usize) -> f64 {
+        [v.x, v.y, v.z][k]
+    }
+
+    // `p` is on the face the outward normal `n` points out of
+    fn on_face(b: &Aabb, p: Point3, n: Vec3) -> Result<(), TestCaseError> {
+        let scale = 1e-9 * (1.0 + p.length());
+        for k in 0..3 {
+            let (lo, hi, x) = (axis(b.min, k), axis(b.max, k), axis(p, k));
+            prop_assert!(
+                x >= lo - scale && x <= hi + scale,
+                "{:?} outside the box",
+                p
+            );
+            match axis(n, k) {
+                1.0 => prop_assert!((x - hi).abs() <= scale, "{:?} not on the +{} face", p, k),
+                -1.0 => prop_assert!((x - lo).abs() <= scale, "{:?} not on the -{} face", p, k),
+                0.0 => {}
+                _ => prop_assert!(false, "{:?} is not a face normal", n),
+            }
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn hits_lie_on_the_face_of_their_normal(
+            b in boxes(),
+            origin in point(10.0),
+            dir in any_direction(),
+            t_min in 0.0..1e-3,
+            t_max in 1.0..1e3,
+        ) {
+            let cube = cube(b);
+            let r = Ray::new(origin, dir);
+            if let Some(rec) = cube.hit(&r, t_min, t_max) {
+                check_hit(&rec, &r, t_min, t_max)?;
+                on_face(&b, rec.p, outward(&rec))?;
+            }
+        }
+
+        #[test]
+        fn rays_aimed_at_the_box_hit_its_outside(
+            b in boxes(),
+            origin in point(10.0),
+            target in point(0.99),
+            len in 0.01..100.0,
+        ) {
+            let cube = cube(b);
+            let inside = |p: Point3| (0..3).all(|k| axis(p, k) > axis(b.min, k) && axis(p, k) < axis(b.max, k));
+            prop_assume!(!inside(origin));
+            let half = (b.max - b.min) * 0.5;
+            let target = b.min + half + Vec3::new(target.x * half.x, target.y * half.y, target.z * half.z);
+            let r = Ray::new(origin, (target - origin).unit() * len);
+            let rec = cube.hit(&r, 1e-9, f64::INFINITY);
+            prop_assert!(rec.is_some(), "missed");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 1e-9, f64::INFINITY)?;
+            prop_assert!(rec.front_face);
+            on_face(&b, rec.p, outward(&rec))?;
+        }
+
+        #[test]
+        fn rays_from_inside_hit_exactly_once(
+            b in boxes(),
+            start in point(0.99),
+            dir in any_direction(),
+        ) {
+            let cube = cube(b);
+            let half = (b.max - b.min) * 0.5;
+            let origin = b.min + half + Vec3::new(start.x * half.x, start.y * half.y, start.z * half.z);
+            let r = Ray::new(origin, dir);
+            let rec = cube.hit(&r, 0.0, f64::INFINITY);
+            prop_assert!(rec.is_some(), "no exit hit");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 0.0, f64::INFINITY)?;
+            prop_assert!(!rec.front_face);
+            on_face(&b, rec.p, outward(&rec))?;
+            let beyond = Ray::new(rec.p, dir.unit());
+            prop_assert!(cube.hit(&beyond, 1e-6, f64::INFINITY).is_none(), "hit twice");
+        }
+    }
+}
@@ -1,13 +1,15 @@
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, Shape};
+use crate::material::Material;
 use crate::math::{Color, Point3, Vec3};
 use crate::ray::Ray;
+use crate::stats::{self, Prim};
+use std::f64::consts::PI;
 
 pub struct Cylinder {
     pub center: Point3, // center of the cylinder (at mid-height)
     pub radius: f64,
     pub half_height: f64, // height/2 along +Y / -Y
-    pub albedo: Color,
-    pub reflectivity: f64,
+    pub material: Material,
 }
 
 impl Cylinder {
@@ -17,19 +19,33 @@ impl Cylinder {
         half_height: f64,
         albedo: Color,
         reflectivity: f64,
+    ) -> Self {
+        Self::with_material(
+            center,
+            radius,
+            half_height,
+            Material::new(albedo, reflectivity),
+        )
+    }
+
+    pub fn with_material(
+        center: Point3,
+        radius: f64,
+        half_height: f64,
+        material: Material,
     ) -> Self {
         Self {
             center,
             radius,
             half_height,
-            albedo,
-            reflectivity,
+            material,
         }
     }
 }
 
 impl Hittable for Cylinder {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_tests(Prim::Cylinder, 1);
         // Transform ray into cylinder's local space (axis = Y, centered at self.center)
         let ro = r.origin - self.center;
         let rd = r.direction;
@@ -60,14 +76,17 @@ impl Hittable for Cylinder {
                                 (ro.z + t * rd.z) / self.radius,
                             )
                             .unit();
-                            let rec = HitRecord::with_face_normal(
-                                r,
-                                p,
-                                outward,
-                                t,
-                                self.albedo,
-                                self.reflectivity,
-                            );
+                            // u wraps around the axis (counterclockwise seen from -Y), v runs up
+                            let (lx, lz) = (ro.x + t * rd.x, ro.z + t * rd.z);
+                            let phi = (-lz).atan2(lx).rem_euclid(2.0 * PI);
+                            let h = 2.0 * self.half_height;
+                            let rec = HitRecord::with_face_normal(r, p, outward, t, &self.material)
+                                .with_uv(
+                                    phi / (2.0 * PI),
+                                    (y + self.half_height) / h,
+                                    Vec3::new(lz, 0.0, -lx) * (2.0 * PI),
+                                    Vec3::new(0.0, h, 0.0),
+                                );
                             t_hit = t;
                             hit_rec = Some(rec);
                         }
@@ -82,55 +101,52 @@ impl Hittable for Cylinder {
         }
 
         // ----- Caps: planes at y = ±half_height -----
-        let check_cap = |y_cap: f64,
-                         r: &Ray,
-                         ro: Vec3,
-                         rd: Vec3,
-                         this: &Cylinder,
-                         t_min: f64,
-                         t_hit: &mut f64,
-                         hit_rec: &mut Option<HitRecord>| {
-            const EPS: f64 = 1e-12;
-            if rd.y.abs() < EPS {
-                return;
-            } // ray parallel to cap plane
-            let t = (y_cap - ro.y) / rd.y;
-            if t < t_min || t > *t_hit {
-                return;
-            }
-            let x = ro.x + t * rd.x;
-            let z = ro.z + t * rd.z;
-            if x * x + z * z <= this.radius * this.radius + 1e-9 {
-                let p = r.at(t);
-                // outward normal: up for top cap, down for bottom cap
-                let outward = if y_cap > 0.0 {
-                    Vec3::new(0.0, 1.0, 0.0)
-                } else {
-                    Vec3::new(0.0, -1.0, 0.0)
-                };
-                let rec =
-                    HitRecord::with_face_normal(r, p, outward, t, this.albedo, this.reflectivity);
-                *t_hit = t;
-                *hit_rec = Some(rec);
-            }
-        };
+        let check_cap =
+            |y_cap: f64,
+             r: &Ray,
+             ro: Vec3,
+             rd: Vec3,
+             t_min: f64,
+             t_hit: &mut f64,
+             hit_rec: &mut Option<_>| {
+                const EPS: f64 = 1e-12;
+                if rd.y.abs() < EPS {
+                    return;
+                } // ray parallel to cap plane
+                let t = (y_cap - ro.y) / rd.y;
+                if t < t_min || t > *t_hit {
+                    return;
+                }
+                let x = ro.x + t * rd.x;
+                let z = ro.z + t * rd.z;
+                if x * x + z * z <= self.radius * self.radius + 1e-9 {
+                    let p = r.at(t);
+                    // outward normal: up for top cap, down for bottom cap
+                    let outward = if y_cap > 0.0 {
+                        Vec3::new(0.0, 1.0, 0.0)
+                    } else {
+                        Vec3::new(0.0, -1.0, 0.0)
+                    };
+                    // Caps map the disc onto [0,1]^2 as seen from outside (-Z up on the top cap)
+                    let d = 2.0 * self.radius;
+                    let (v, dpdv) = if y_cap > 0.0 {
+                        (0.5 - z / d, Vec3::new(0.0, 0.0, -d))
+                    } else {
+                        (0.5 + z / d, Vec3::new(0.0, 0.0, d))
+                    };
+                    let rec = HitRecord::with_face_normal(r, p, outward, t, &self.material)
+                        .with_uv(0.5 + x / d, v, Vec3::new(d, 0.0, 0.0), dpdv);
+                    *t_hit = t;
+                    *hit_rec = Some(rec);
+                }
+            };
 
-        check_cap(
-            self.half_height,
-            r,
-            ro,
-            rd,
-            self,
-            t_min,
-            &mut t_hit,
-            &mut hit_rec,
-        );
+        check_cap(self.half_height, r, ro, rd, t_min, &mut t_hit, &mut hit_rec);
         check_cap(
             -self.half_height,
             r,
             ro,
             rd,
-            self,
             t_min,
             &mut t_hit,
             &mut hit_rec,
@@ -139,3 +155,157 @@ impl Hittable for Cylinder {
         hit_rec
     }
 }
+
+impl Shape for Cylinder {
+    fn area(&self) -> f64 {
+        let side = 2.0 * PI * self.radius * (2.0 * self.half_height);
+        let cap = PI * self.radius * self.radius;
+        side + 2.0 * cap
+    }
+
+    fn sample_surface(&self, u: (f64, f64)) -> (Point3, Vec3) {
+        let side = 2.0 * PI * self.radius * (2.0 * self.half_height);
+        let cap = PI * self.radius * self.radius;
+        let pick = u.0 * (side + 2.0 * cap);
+        let phi = 2.0 * PI * u.1;
+        if pick < side {
+            let y = -self.half_height + (pick / side) * 2.0 * self.half_height;
+            let n = Vec3::new(phi.cos(), 0.0, phi.sin());
+            let p = self.center + Vec3::new(n.x * self.radius, y, n.z * self.radius);
+            (p, n)
+        } else {
+            // Top cap for the first half of the remaining range, bottom for the rest
+            let rest = pick - side;
+            let (y, ny, s) = if rest < cap {
+                (self.half_height, 1.0, rest / cap)
+            } else {
+                (-self.half_height, -1.0, (rest - cap) / cap)
+            };
+            let r = self.radius * s.clamp(0.0, 1.0).sqrt();
+            let p = self.center + Vec3::new(r * phi.cos(), y, r * phi.sin());
+            (p, Vec3::new(0.0, ny, 0.0))
+        }
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::testing::{check_hit, direction, outward, point, unit};
+    use proptest::prelude::*;
+
+    fn cylinder(center: Point3, radius: f64, half_height: f64) -> Cylinder {
+        Cylinder::new(center, radius, half_height, Color::new(0.5, 0.5, 0.5), 0.0)
+    }
+
+    // Along the axis, exactly and almost, which the side test treats specially
+    fn direction_near_axis() -> impl Strategy<Value = Vec3> {
+        (prop::bool::ANY, 0.0..1e-6, unit(), 0.01..100.0).prop_map(|(up, tilt, t, len)| {
+            let y = if up { 1.0 } else { -1.0 };
+            Vec3::new(t.x * tilt, y, t.z * tilt) * len
+        })
+    }
+
+    fn any_direction() -> impl Strategy<Value = Vec3> {
+        prop_oneof![3 => direction(), 1 => direction_near_axis()]
+    }
+
+    // Point in the solid, given in [-1, 1]^3 and scaled to the cylinder
+    fn inside(c: &Cylinder, q: Vec3) -> Point3 {
+        let (x, z) = (q.x * (1.0 - q.z.abs() * 0.5), q.z * (1.0 - q.x.abs() * 0.5));
+        c.center + Vec3::new(x * c.radius * 0.7, q.y * c.half_height, z * c.radius * 0.7)
+    }
+
+    // `p` is on the side or a cap, and `n` is the outward normal there
+    fn on_surface(c: &Cylinder, p: Point3, n: Vec3) -> Result<(), TestCaseError> {
+        let l = p - c.center;
+        let rho = (l.x * l.x + l.z * l.z).sqrt();
+        let tol = 1e-9 * (1.0 + l.length());
+        let side = (rho - c.radius).abs() <= tol && l.y.abs() <= c.half_height + tol;
+        let cap = (l.y.abs() - c.half_height).abs() <= tol && rho <= c.radius + tol;
+        prop_assert!(side || cap, "{:?} is off the surface", p);
+        if n.y == 0.0 {
+            prop_assert!(side, "side normal at {:?}", p);
+            let radial = Vec3::new(l.x, 0.0, l.z) / rho;
+            prop_assert!(
+                (Vec3::dot(n, radial) - 1.0).abs() < 1e-9,
+                "{:?} isn't radial",
+                n
+            );
+        } else {
+            prop_assert!(cap, "cap normal at {:?}", p);
+            prop_assert!(
+                n.y == l.y.signum() && n.x == 0.0 && n.z == 0.0,
+                "{:?} isn't the cap normal",
+                n
+            );
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn hits_lie_on_the_surface(
+            center in point(2.0),
+            radius in 0.05..2.0,
+            half_height in 0.05..2.0,
+            origin in point(10.0),
+            dir in any_direction(),
+            t_min in 0.0..1e-3,
+            t_max in 1.0..1e3,
+        ) {
+            let c = cylinder(center, radius, half_height);
+            let r = Ray::new(origin, dir);
+            if let Some(rec) = c.hit(&r, t_min, t_max) {
+                check_hit(&rec, &r, t_min, t_max)?;
+                on_surface(&c, rec.p, outward(&rec))?;
+            }
+        }
+
+        #[test]
+        fn rays_aimed_at_the_cylinder_hit_its_outside(
+            center in point(2.0),
+            radius in 0.05..2.0,
+            half_height in 0.05..2.0,
+            from in unit(),
+            distance in 1.01..20.0,
+            target in point(1.0),
+            len in 0.01..100.0,
+        ) {
+            let c = cylinder(center, radius, half_height);
+            // Outside the bounding sphere, so outside the solid
+            let origin = center + from * (radius.hypot(half_height) * distance);
+            let r = Ray::new(origin, (inside(&c, target) - origin).unit() * len);
+            let rec = c.hit(&r, 1e-9, f64::INFINITY);
+            prop_assert!(rec.is_some(), "missed");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 1e-9, f64::INFINITY)?;
+            prop_assert!(rec.front_face);
+            on_surface(&c, rec.p, outward(&rec))?;
+        }
+
+        #[test]
+        fn rays_from_inside_hit_exactly_once(
+            center in point(2.0),
+            radius in 0.05..2.0,
+            half_height in 0.05..2.0,
+            start in point(0.99),
+            dir in any_direction(),
+        ) {
+            let c = cylinder(center, radius, half_height);
+            let r = Ray::new(inside(&c, start), dir);
+            let rec = c.hit(&r, 0.0, f64::INFINITY);
+            prop_assert!(rec.is_some(), "no exit hit");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 0.0, f64::INFINITY)?;
+            prop_assert!(!rec.front_face);
+            on_surface(&c, rec.p, outward(&rec))?;
+            let beyond = Ray::new(rec.p, dir.unit());
+            prop_assert!(c.hit(&beyond, 1e-6, f64::INFINITY).is_none(), "hit twice");
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::checkpoint::{put_f64, put_u32, Reader};
+use crate::film::Rect;
+use crate::math::{luminance, Color, Vec3};
+
+/// Per-pixel averages of what the camera rays hit first: surface albedo,
+/// shading normal, and the fraction of rays that hit anything. Rays that miss
+/// record white albedo and a zero normal.
+pub struct Features {
+    pub width: usize,
+    pub height: usize,
+    albedo: Vec<Color>,
+    normal: Vec<Vec3>,
+    hits: Vec<u32>,
+    count: Vec<u32>,
+}
+
+impl Features {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            albedo: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            normal: vec![Vec3::new(0.0, 0.0, 0.0); width * height],
+            hits: vec![0; width * height],
+            count: vec![0; width * height],
+        }
+    }
+
+    /// Records one primary hit for pixel `(i, j)`, `j` counting from the bottom.
+    pub fn add(&mut self, i: usize, j: usize, albedo: Color, normal: Vec3) {
+        let idx = j * self.width + i;
+        self.albedo[idx] += albedo;
+        self.normal[idx] += normal;
+        if normal.length_squared() > 0.0 {
+            self.hits[idx] += 1;
+        }
+        self.count[idx] += 1;
+    }
+
+    /// Copy of the features inside `rect`.
+    pub fn crop(&self, rect: Rect) -> Self {
+        let mut out = Self::new(rect.width(), rect.height());
+        for (k, (i, j)) in rect.pixels().enumerate() {
+            let idx = j * self.width + i;
+            out.albedo[k] = self.albedo[idx];
+            out.normal[k] = self.normal[idx];
+            out.hits[k] = self.hits[idx];
+            out.count[k] = self.count[idx];
+        }
+        out
+    }
+
+    fn albedo(&self, idx: usize) -> Color {
+        let n = self.count[idx].max(1) as f64;
+        self.albedo[idx] / n
+    }
+
+    pub const SAVED_PIXEL_BYTES: usize = 56;
+
+    /// Appends the feature sums of `rect`.
+    pub fn save_rect(&self, rect: Rect, out: &mut Vec<u8>) {
+        for (i, j) in rect.pixels() {
+            let idx = j * self.width + i;
+            let (a, n) = (self.albedo[idx], self.normal[idx]);
+            for v in [a.x, a.y, a.z, n.x, n.y, n.z] {
+                put_f64(out, v);
+            }
+            put_u32(out, self.hits[idx]);
+            put_u32(out, self.count[idx]);
+        }
+    }
+
+    /// Replaces `rect` with what `save_rect` wrote for it.
+    pub fn load_rect(&mut self, rect: Rect, r: &mut Reader) -> Result<(), String> {
+        if rect.x1 > self.width || rect.y1 > self.height {
+            return Err("rectangle outside the feature buffers".into());
+        }
+        for (i, j) in rect.pixels() {
+            let idx = j * self.width + i;
+            self.albedo[idx] = Color::new(r.f64()?, r.f64()?, r.f64()?);
+            self.normal[idx] = Vec3::new(r.f64()?, r.f64()?, r.f64()?);
+            self.hits[idx] = r.u32()?;
+            self.count[idx] = r.u32()?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_rect(&mut self, rect: Rect) {
+        for (i, j) in rect.pixels() {
+            let idx = j * self.width + i;
+            self.albedo[idx] = Color::new(0.0, 0.0, 0.0);
+            self.normal[idx] = Vec3::new(0.0, 0.0, 0.0);
+            self.hits[idx] = 0;
+            self.count[idx] = 0;
+        }
+    }
+
+    fn coverage(&self, idx: usize) -> f64 {
+        self.hits[idx] as f64 / self.count[idx].max(1) as f64
+    }
+
+    // Unit average normal, or zero for background pixels
+    fn normal(&self, idx: usize) -> Vec3 {
+        let n = self.normal[idx];
+        if n.length_squared() < 1e-12 {
+            n
+        } else {
+            n.unit()
+        }
+    }
+}
+
+/// `--denoise` settings.
+#[derive(Clone, Copy, Debug)]
+pub struct DenoiseOpts {
+    pub radius: usize, // half-width of the filter window in pixels
+}
+
+impl Default for DenoiseOpts {
+    fn default() -> Self {
+        Self { radius: 5 }
+    }
+}
+
+// Feature tolerances: albedo and coverage differences, normal lobe exponent,
+// and how many standard deviations two pixel colors may differ by
+const SIGMA_ALBEDO: f64 = 0.1;
+const SIGMA_COVERAGE: f64 = 0.1;
+const NORMAL_POWER: i32 = 64;
+const SIGMA_COLOR: f64 = 2.0;
+// Keeps dark albedo from blowing up the demodulated lighting
+const ALBEDO_EPS: f64 = 0.01;
+
+/// Joint bilateral filter over `image` (rows bottom to top, linear radiance).
+///
+/// Lighting is separated from texture by dividing by the albedo, filtered,
+/// and multiplied back, so texture detail stays sharp. Neighbors count less
+/// the farther they are, the more their albedo, normal or coverage differs (so
+/// silhouettes against the background stay crisp), and the more
+/// their luminance differs relative to the noise both pixels are expected to
+/// have; `variance` is the variance of each pixel's mean luminance (infinite
+/// if unknown, which disables the color term).
+pub fn denoise(
+    image: &[Color],
+    variance: &[f64],
+    features: &Features,
+    opts: DenoiseOpts,
+) -> Vec<Color> {
+    let (w, h) = (features.width, features.height);
+    let r = opts.radius as i64;
+    let sigma_s = (opts.radius as f64 / 2.0).max(0.5);
+    let demod = |c: Color, a: Color| {
+        Color::new(
+            c.x / a.x.max(ALBEDO_EPS),
+            c.y / a.y.max(ALBEDO_EPS),
+            c.z / a.z.max(ALBEDO_EPS),
+        )
+    };
+    let remod = |c: Color, a: Color| {
+        Color::new(
+            c.x * a.x.max(ALBEDO_EPS),
+            c.y * a.y.max(ALBEDO_EPS),
+            c.z * a.z.max(ALBEDO_EPS),
+        )
+    };
+
+    let albedo: Vec<Color> = (0..w * h).map(|i| features.albedo(i)).collect();
+    let normal: Vec<Vec3> = (0..w * h).map(|i| features.normal(i)).collect();
+    let coverage: Vec<f64> = (0..w * h).map(|i| features.coverage(i)).collect();
+    let lighting: Vec<Color> = (0..w * h).map(|i| demod(image[i], albedo[i])).collect();
+
+    let mut out = Vec::with_capacity(w * h);
+    for j in 0..h as i64 {
+        for i in 0..w as i64 {
+            let p = j as usize * w + i as usize;
+            let (ap, np, cp) = (albedo[p], normal[p], coverage[p]);
+            let lp = luminance(image[p]);
+            let var = blurred_variance(variance, w, h, i, j);
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut wsum = 0.0;
+            for qj in (j - r).max(0)..=(j + r).min(h as i64 - 1) {
+                for qi in (i - r).max(0)..=(i + r).min(w as i64 - 1) {
+                    let q = qj as usize * w + qi as usize;
+                    let d2 = ((qi - i).pow(2) + (qj - j).pow(2)) as f64;
+                    let mut wt = (-d2 / (2.0 * sigma_s * sigma_s)).exp();
+
+                    let da = albedo[q] - ap;
+                    wt *= (-da.length_squared() / (2.0 * SIGMA_ALBEDO * SIGMA_ALBEDO)).exp();
+
+                    let dc = coverage[q] - cp;
+                    wt *= (-dc * dc / (2.0 * SIGMA_COVERAGE * SIGMA_COVERAGE)).exp();
+
+                    // Background pixels have no normal to compare
+                    let nq = normal[q];
+                    if np.length_squared() > 0.0 && nq.length_squared() > 0.0 {
+                        wt *= Vec3::dot(np, nq).max(0.0).powi(NORMAL_POWER);
+                    }
+
+                    if var.is_finite() {
+                        let dl = luminance(image[q]) - lp;
+                        wt *= (-dl * dl / (2.0 * SIGMA_COLOR * SIGMA_COLOR * var + 1e-10)).exp();
+                    }
+
+                    sum += lighting[q] * wt;
+                    wsum += wt;
+                }
+            }
+            // The center pixel always has weight 1, so wsum > 0
+            out.push(remod(sum / wsum, ap));
+        }
+    }
+    out
+}
+
+// 3x3 box average of the variance around (i, j); a few samples per pixel give
+// too rough an estimate on their own
+fn blurred_variance(variance: &[f64], w: usize, h: usize, i: i64, j: i64) -> f64 {
+    let mut sum = 0.0;
+    let mut n = 0.0;
+    for qj in (j - 1).max(0)..=(j + 1).min(h as i64 - 1) {
+        for qi in (i - 1).max(0)..=(i + 1).min(w as i64 - 1) {
+            sum += variance[qj as usize * w + qi as usize];
+            n += 1.0;
+        }
+    }
+    sum / n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: usize = 16;
+
+    // Mean and variance of the luminance of the columns in `cols`
+    fn column_stats(image: &[Color], cols: std::ops::Range<usize>) -> (f64, f64) {
+        let values: Vec<f64> = (0..W * W)
+            .filter(|k| cols.contains(&(k % W)))
+            .map(|k| luminance(image[k]))
+            .collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        (
+            mean,
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n,
+        )
+    }
+
+    #[test]
+    fn flat_noise_is_smoothed_without_crossing_an_albedo_edge() {
+        // Evenly lit plane, dark gray on the left half and light on the right,
+        // with +-30% noise per pixel
+        let mut features = Features::new(W, W);
+        let mut image = Vec::new();
+        for k in 0..W * W {
+            let a = if k % W < W / 2 { 0.2 } else { 0.8 };
+            features.add(k % W, k / W, Color::new(a, a, a), Vec3::new(0.0, 0.0, 1.0));
+            let noise = (k as u64 * 2_654_435_761 % 1000) as f64 / 1000.0 - 0.5;
+            let v = a * (1.0 + 0.6 * noise);
+            image.push(Color::new(v, v, v));
+        }
+        let variance: Vec<f64> = (0..W * W)
+            .map(|k| if k % W < W / 2 { 0.2f64 } else { 0.8 }.powi(2) * 0.03)
+            .collect();
+        let out = denoise(&image, &variance, &features, DenoiseOpts::default());
+
+        for (cols, a) in [(0..W / 2, 0.2), (W / 2..W, 0.8)] {
+            let (_, noisy) = column_stats(&image, cols.clone());
+            let (mean, smooth) = column_stats(&out, cols.clone());
+            assert!(
+                smooth < noisy / 10.0,
+                "{cols:?}: variance {noisy} -> {smooth}"
+            );
+            assert!((mean - a).abs() < 0.02 * a, "{cols:?}: mean {mean}");
+        }
+        // The columns next to the edge keep their own albedo
+        let (left, _) = column_stats(&out, W / 2 - 1..W / 2);
+        let (right, _) = column_stats(&out, W / 2..W / 2 + 1);
+        assert!(
+            (left - 0.2).abs() < 0.01 && (right - 0.8).abs() < 0.04,
+            "{left} | {right}"
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use crate::adaptive::heat_color;
+use crate::image_io::{load_image, srgb_to_linear, Image};
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const USAGE: &str = "Usage: rt diff [FLAGS] <reference> <test>
+
+Compares two PNG/PPM images of the same size and prints RMSE, PSNR, SSIM and
+FLIP error, then writes the FLIP error map in false color (black = no visible
+difference, white = maximal). Exits with 1 if a threshold is exceeded, 2 on
+errors, 0 otherwise.
+
+  --out=<file.ppm>            Error map (default diff.ppm)
+  --ppd=<x>                   Viewing condition for FLIP, in pixels per degree
+                              (default 67: a 4K 0.7 m wide monitor at 0.7 m)
+  --max-flip=<x>              Fail above this mean FLIP error (default 0.05)
+  --max-rmse=<x>              Fail above this RMSE, in 8-bit values
+  --min-psnr=<dB>             Fail below this PSNR
+  --min-ssim=<x>              Fail below this SSIM
+";
+
+/// Image metrics; RMSE and PSNR are over 8-bit channel values.
+pub struct Metrics {
+    pub rmse: f64,
+    pub psnr: f64,
+    pub ssim: f64,
+    pub flip_mean: f64,
+    pub flip_max: f64,
+    pub flip_max_at: (usize, usize),
+}
+
+/// `rt diff`: `args` are the arguments after the subcommand. Returns whether
+/// the images are within the thresholds.
+pub fn run(args: &[String]) -> Result<bool, String> {
+    let mut out = "diff.ppm".to_string();
+    let mut ppd = 67.0;
+    let mut max_flip = Some(0.05);
+    let mut max_rmse = None;
+    let mut min_psnr = None;
+    let mut min_ssim = None;
+    let mut inputs = Vec::new();
+    for a in args {
+        // "--flag=x" as a number, warning if it isn't one
+        let num = |prefix: &str| {
+            let v = a.strip_prefix(prefix)?;
+            let n = v.parse::<f64>().ok().filter(|n| n.is_finite());
+            if n.is_none() {
+                eprintln!("warning: ignoring {prefix}{v}, expected a number");
+            }
+            Some(n)
+        };
+        if let Some(v) = a.strip_prefix("--out=") {
+            out = v.to_string();
+        } else if let Some(v) = num("--ppd=") {
+            ppd = v.filter(|&p| p > 0.0).unwrap_or(ppd);
+        } else if let Some(v) = num("--max-flip=") {
+            max_flip = v.or(max_flip);
+        } else if let Some(v) = num("--max-rmse=") {
+            max_rmse = v.or(max_rmse);
+        } else if let Some(v) = num("--min-psnr=") {
+            min_psnr = v.or(min_psnr);
+        } else if let Some(v) = num("--min-ssim=") {
+            min_ssim = v.or(min_ssim);
+        } else if a == "--help" {
+            eprint!("{USAGE}");
+            return Ok(true);
+        } else if a.starts_with("--") {
+            eprintln!("warning: unknown diff flag {a}");
+        } else {
+            inputs.push(a.as_str());
+        }
+    }
+    let [reference, test] = inputs[..] else {
+        return Err(format!("expected two images\n\n{USAGE}"));
+    };
+    let a = load_image(reference)?;
+    let b = load_image(test)?;
+    if (a.width, a.height) != (b.width, b.height) {
+        return Err(format!(
+            "{reference} is {}x{}, {test} is {}x{}",
+            a.width, a.height, b.width, b.height
+        ));
+    }
+
+    let (m, flip_map) = compare(&a, &b, ppd);
+    println!("RMSE  {:.4}", m.rmse);
+    println!("PSNR  {:.2} dB", m.psnr);
+    println!("SSIM  {:.5}", m.ssim);
+    println!(
+        "FLIP  {:.5} (max {:.4} at {},{})",
+        m.flip_mean, m.flip_max, m.flip_max_at.0, m.flip_max_at.1
+    );
+    write_error_map(&out, a.width, a.height, &flip_map)
+        .map_err(|e| format!("could not write {out}: {e}"))?;
+    eprintln!("wrote {out}");
+
+    let mut ok = true;
+    let mut check = |failed: bool, what: String| {
+        if failed {
+            eprintln!("fail: {what}");
+            ok = false;
+        }
+    };
+    if let Some(t) = max_flip {
+        check(m.flip_mean > t, format!("FLIP {:.5} > {t}", m.flip_mean));
+    }
+    if let Some(t) = max_rmse {
+        check(m.rmse > t, format!("RMSE {:.4} > {t}", m.rmse));
+    }
+    if let Some(t) = min_psnr {
+        check(m.psnr < t, format!("PSNR {:.2} dB < {t} dB", m.psnr));
+    }
+    if let Some(t) = min_ssim {
+        check(m.ssim < t, format!("SSIM {:.5} < {t}", m.ssim));
+    }
+    Ok(ok)
+}
+
+/// Computes the metrics and the per-pixel FLIP error (rows top to bottom).
+/// `ppd` is the number of pixels per degree of visual angle.
+pub fn compare(a: &Image, b: &Image, ppd: f64) -> (Metrics, Vec<f64>) {
+    let (w, h) = (a.width, a.height);
+    let sq: f64 = a
+        .pixels
+        .iter()
+        .zip(&b.pixels)
+        .map(|(p, q)| ((*p - *q) * 255.0).length_squared())
+        .sum();
+    let rmse = (sq / (3 * w * h).max(1) as f64).sqrt();
+    let psnr = 20.0 * (255.0 / rmse).log10();
+
+    let flip_map = flip(a, b, ppd);
+    let (at, &flip_max) = flip_map
+        .iter()
+        .enumerate()
+        .reduce(|best, e| if e.1 > best.1 { e } else { best })
+        .unwrap_or((0, &0.0));
+    let metrics = Metrics {
+        rmse,
+        psnr,
+        ssim: ssim(a, b),
+        flip_mean: flip_map.iter().sum::<f64>() / flip_map.len().max(1) as f64,
+        flip_max,
+        flip_max_at: (at % w.max(1), at / w.max(1)),
+    };
+    (metrics, flip_map)
+}
+
+// A w x h grid of values, rows top to bottom
+struct Plane {
+    w: usize,
+    h: usize,
+    v: Vec<f64>,
+}
+
+impl Plane {
+    fn from_fn(img: &Image, f: impl Fn(usize) -> f64) -> Plane {
+        let v = (0..img.pixels.len()).map(f).collect();
+        Plane {
+            w: img.width,
+            h: img.height,
+            v,
+        }
+    }
+
+    // Separable convolution with odd-length kernels, clamping at the edges
+    fn convolve(&self, kx: &[f64], ky: &[f64]) -> Plane {
+        let (w, h) = (self.w, self.h);
+        let pass = |src: &[f64], k: &[f64], horizontal: bool| {
+            let r = (k.len() / 2) as isize;
+            let mut dst = vec![0.0; w * h];
+            for y in 0..h {
+                for x in 0..w {
+                    let (pos, len) = if horizontal { (x, w) } else { (y, h) };
+                    let mut sum = 0.0;
+                    for (i, &kv) in k.iter().enumerate() {
+                        let p = (pos as isize + i as isize - r).clamp(0, len as isize - 1) as usize;
+                        let idx = if horizontal { y * w + p } else { p * w + x };
+                        sum += kv * src[idx];
+                    }
+                    dst[y * w + x] = sum;
+                }
+            }
+            dst
+        };
+        let v = pass(&pass(&self.v, kx, true), ky, false);
+        Plane { w, h, v }
+    }
+
+    fn map2(&self, other: &Plane, f: impl Fn(f64, f64) -> f64) -> Plane {
+        let v = self
+            .v
+            .iter()
+            .zip(&other.v)
+            .map(|(&a, &b)| f(a, b))
+            .collect();
+        Plane {
+            w: self.w,
+            h: self.h,
+            v,
+        }
+    }
+}
+
+fn gaussian(sigma: f64, radius: usize) -> Vec<f64> {
+    let k: Vec<f64> = (-(radius as isize)..=radius as isize)
+        .map(|x| (-((x * x) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = k.iter().sum();
+    k.into_iter().map(|v| v / sum).collect()
+}
+
+// Mean SSIM of the luma, with the usual 11-tap Gaussian window (sigma 1.5)
+fn ssim(a: &Image, b: &Image) -> f64 {
+    let luma = |img: &Image| {
+        Plane::from_fn(img, |i| {
+            let c = img.pixels[i];
+            0.299 * c.x + 0.587 * c.y + 0.114 * c.z
+        })
+    };
+    let (x, y) = (luma(a), luma(b));
+    let g = gaussian(1.5, 5);
+    let blur = |p: &Plane| p.convolve(&g, &g);
+    let (mx, my) = (blur(&x), blur(&y));
+    let sxx = blur(&x.map2(&x, |a, b| a * b));
+    let syy = blur(&y.map2(&y, |a, b| a * b));
+    let sxy = blur(&x.map2(&y, |a, b| a * b));
+    let (c1, c2) = (0.01f64.powi(2), 0.03f64.powi(2));
+    let sum: f64 = (0..x.v.len())
+        .map(|i| {
+            let (mx, my) = (mx.v[i], my.v[i]);
+            let (vx, vy, cov) = (sxx.v[i] - mx * mx, syy.v[i] - my * my, sxy.v[i] - mx * my);
+            ((2.0 * mx * my + c1) * (2.0 * cov + c2)) / ((mx * mx + my * my + c1) * (vx + vy + c2))
+        })
+        .sum();
+    sum / x.v.len().max(1) as f64
+}
+
+// FLIP (Andersson et al. 2020), LDR version. Both images are filtered with the
+// eye's contrast sensitivity in a linear opponent space, then compared in
+// Hunt-adjusted L*a*b*; the color error is amplified where edges or points
+// differ.
+
+const WHITE: [f64; 3] = [0.950428545, 1.0, 1.088900371]; // D65 XYZ
+
+fn rgb_to_xyz(c: [f64; 3]) -> [f64; 3] {
+    [
+        0.4124564 * c[0] + 0.3575761 * c[1] + 0.1804375 * c[2],
+        0.2126729 * c[0] + 0.7151522 * c[1] + 0.0721750 * c[2],
+        0.0193339 * c[0] + 0.1191920 * c[1] + 0.9503041 * c[2],
+    ]
+}
+
+fn xyz_to_rgb(c: [f64; 3]) -> [f64; 3] {
+    [
+        3.2404542 * c[0] - 1.5371385 * c[1] - 0.4985314 * c[2],
+        -0.9692660 * c[0] + 1.8760108 * c[1] + 0.0415560 * c[2],
+        0.0556434 * c[0] - 0.2040259 * c[1] + 1.0572252 * c[2],
+    ]
+}
+
+fn xyz_to_ycxcz(c: [f64; 3]) -> [f64; 3] {
+    let [x, y, z] = [c[0] / WHITE[0], c[1] / WHITE[1], c[2] / WHITE[2]];
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+fn ycxcz_to_xyz(c: [f64; 3]) -> [f64; 3] {
+    let y = (c[0] + 16.0) / 116.0;
+    [
+        (c[1] / 500.0 + y) * WHITE[0],
+        y * WHITE[1],
+        (y - c[2] / 200.0) * WHITE[2],
+    ]
+}
+
+fn xyz_to_lab(c: [f64; 3]) -> [f64; 3] {
+    let f = |t: f64| {
+        if t > (6.0f64 / 29.0).powi(3) {
+            t.cbrt()
+        } else {
+            t / (3.0 * (6.0f64 / 29.0).powi(2)) + 4.0 / 29.0
+        }
+    };
+    let [x, y, z] = [f(c[0] / WHITE[0]), f(c[1] / WHITE[1]), f(c[2] / WHITE[2])];
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+// L*a*b* with the chroma scaled by lightness, as dark colors look less saturated
+fn hunt_lab(rgb: [f64; 3]) -> [f64; 3] {
+    let [l, a, b] = xyz_to_lab(rgb_to_xyz(rgb.map(|v| v.clamp(0.0, 1.0))));
+    [l, 0.01 * l * a, 0.01 * l * b]
+}
+
+fn hyab(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (a[0] - b[0]).abs() + (a[1] - b[1]).hypot(a[2] - b[2])
+}
+
+// Contrast sensitivity filter for one opponent channel: a sum of Gaussians
+// a * sqrt(pi / b) * exp(-pi^2 r^2 / b), r in degrees, normalized to sum to 1.
+// Returns each Gaussian's 1D kernel with its share of the total weight.
+fn csf(components: &[(f64, f64)], ppd: f64) -> Vec<(f64, Vec<f64>)> {
+    let b_max = components.iter().map(|c| c.1).fold(0.0, f64::max);
+    let radius = (3.0 * (b_max / (2.0 * PI * PI)).sqrt() * ppd).ceil() as isize;
+    let kernels: Vec<(f64, Vec<f64>)> = components
+        .iter()
+        .map(|&(a, b)| {
+            let k: Vec<f64> = (-radius..=radius)
+                .map(|i| (-(PI * i as f64 / ppd).powi(2) / b).exp())
+                .collect();
+            let sum: f64 = k.iter().sum();
+            let weight = a * (PI / b).sqrt() * sum * sum;
+            (weight, k.into_iter().map(|v| v / sum).collect())
+        })
+        .collect();
+    let total: f64 = kernels.iter().map(|k| k.0).sum();
+    kernels.into_iter().map(|(w, k)| (w / total, k)).collect()
+}
+
+fn flip(a: &Image, b: &Image, ppd: f64) -> Vec<f64> {
+    const QC: f64 = 0.7; // color error exponent
+    const PC: f64 = 0.4; // share of the maximum error mapped below PT
+    const PT: f64 = 0.95;
+    const QF: f64 = 0.5; // feature error exponent
+    const FEATURE_WIDTH: f64 = 0.082; // degrees
+
+    let filters = [
+        csf(&[(1.0, 0.0047)], ppd),
+        csf(&[(1.0, 0.0053)], ppd),
+        csf(&[(34.1, 0.04), (13.5, 0.025)], ppd),
+    ];
+    let linear = |img: &Image, i: usize| {
+        let c = img.pixels[i];
+        [c.x, c.y, c.z].map(srgb_to_linear)
+    };
+    // Filtered image in Hunt-adjusted L*a*b*, and luminance for features
+    let prepare = |img: &Image| {
+        let opp: Vec<[f64; 3]> = (0..img.pixels.len())
+            .map(|i| xyz_to_ycxcz(rgb_to_xyz(linear(img, i))))
+            .collect();
+        let filtered: Vec<Plane> = filters
+            .iter()
+            .enumerate()
+            .map(|(ch, kernels)| {
+                let p = Plane::from_fn(img, |i| opp[i][ch]);
+                let mut out = Plane::from_fn(img, |_| 0.0);
+                for (weight, k) in kernels {
+                    out = out.map2(&p.convolve(k, k), |o, v| o + weight * v);
+                }
+                out
+            })
+            .collect();
+        let lab: Vec<[f64; 3]> = (0..img.pixels.len())
+            .map(|i| {
+                let c = [filtered[0].v[i], filtered[1].v[i], filtered[2].v[i]];
+                hunt_lab(xyz_to_rgb(ycxcz_to_xyz(c)))
+            })
+            .collect();
+        let lum = Plane::from_fn(img, |i| (opp[i][0] + 16.0) / 116.0);
+        (lab, lum)
+    };
+    let (lab_a, lum_a) = prepare(a);
+    let (lab_b, lum_b) = prepare(b);
+
+    // Largest error: between green and blue
+    let cmax = hyab(hunt_lab([0.0, 1.0, 0.0]), hunt_lab([0.0, 0.0, 1.0])).powf(QC);
+    let color_error = |d: f64| {
+        let d = d.powf(QC);
+        if d < PC * cmax {
+            PT * d / (PC * cmax)
+        } else {
+            PT + (1.0 - PT) * (d - PC * cmax) / (cmax - PC * cmax)
+        }
+    };
+
+    // Edges (first derivative of a Gaussian) and points (second derivative),
+    // each normalized so positive weights sum to 1 and negative ones to -1
+    let sigma = 0.5 * FEATURE_WIDTH * ppd;
+    let radius = (3.0 * sigma).ceil() as usize;
+    let g = gaussian(sigma, radius);
+    let normalize = |k: Vec<f64>| {
+        let pos: f64 = k.iter().filter(|&&v| v > 0.0).sum();
+        let neg: f64 = -k.iter().filter(|&&v| v < 0.0).sum::<f64>();
+        k.into_iter()
+            .map(|v| if v > 0.0 { v / pos } else { v / neg })
+            .collect::<Vec<f64>>()
+    };
+    let xs = || (0..g.len()).map(|i| i as f64 - radius as f64);
+    let d1 = normalize(xs().zip(&g).map(|(x, g)| -x * g).collect());
+    let d2 = normalize(
+        xs().zip(&g)
+            .map(|(x, g)| (x * x / (sigma * sigma) - 1.0) * g)
+            .collect(),
+    );
+    let features = |lum: &Plane| {
+        let magnitude = |k: &[f64]| {
+            let gx = lum.convolve(k, &g);
+            let gy = lum.convolve(&g, k);
+            gx.map2(&gy, f64::hypot)
+        };
+        (magnitude(&d1), magnitude(&d2))
+    };
+    let (edges_a, points_a) = features(&lum_a);
+    let (edges_b, points_b) = features(&lum_b);
+
+    (0..lab_a.len())
+        .map(|i| {
+            let ec = color_error(hyab(lab_a[i], lab_b[i]));
+            let df = (edges_a.v[i] - edges_b.v[i])
+                .abs()
+                .max((points_a.v[i] - points_b.v[i]).abs());
+            let ef = (df / 2f64.sqrt()).powf(QF);
+            ec.powf(1.0 - ef)
+        })
+        .collect()
+}
+
+/// Writes per-pixel errors in [0, 1] (rows top to bottom) as a false-color PPM.
+pub fn write_error_map(
+    path: &str,
+    width: usize,
+    height: usize,
+    err: &[f64],
+) -> std::io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "P3")?;
+    writeln!(w, "{width} {height}")?;
+    writeln!(w, "255")?;
+    for &e in err {
+        let [r, g, b] = heat_color(e);
+        writeln!(w, "{r} {g} {b}")?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Color;
+
+    fn image(w: usize, h: usize, f: impl Fn(usize, usize) -> Color) -> Image {
+        let pixels = (0..w * h).map(|i| f(i % w, i / w)).collect();
+        Image {
+            width: w,
+            height: h,
+            pixels,
+        }
+    }
+
+    fn checker(x: usize, y: usize) -> Color {
+        let v = if (x / 4 + y / 4).is_multiple_of(2) {
+            0.8
+        } else {
+            0.2
+        };
+        Color::new(v, v * 0.5, 1.0 - v)
+    }
+
+    #[test]
+    fn identical_images_have_no_error() {
+        let a = image(32, 24, checker);
+        let (m, map) = compare(&a, &a, 67.0);
+        assert_eq!(m.rmse, 0.0);
+        assert!(m.psnr.is_infinite());
+        assert!((m.ssim - 1.0).abs() < 1e-12);
+        assert!(map.iter().all(|&e| e == 0.0));
+    }
+
+    #[test]
+    fn errors_grow_with_the_difference() {
+        let a = image(32, 24, checker);
+        let metrics: Vec<Metrics> = [0.02, 0.1, 0.4]
+            .iter()
+            .map(|shift| {
+                let b = image(32, 24, |x, y| {
+                    let c = checker(x, y);
+                    Color::new((c.x + shift).min(1.0), c.y, c.z)
+                });
+                let (m, map) = compare(&a, &b, 67.0);
+                assert!(map.iter().all(|e| (0.0..=1.0).contains(e)));
+                m
+            })
+            .collect();
+        for m in metrics.windows(2) {
+            assert!(m[1].rmse > m[0].rmse && m[1].psnr < m[0].psnr);
+            assert!(m[1].ssim < m[0].ssim && m[1].flip_mean > m[0].flip_mean);
+        }
+    }
+
+    #[test]
+    fn black_against_white_is_maximal() {
+        let a = image(16, 16, |_, _| Color::new(0.0, 0.0, 0.0));
+        let b = image(16, 16, |_, _| Color::new(1.0, 1.0, 1.0));
+        let (m, _) = compare(&a, &b, 67.0);
+        assert!((m.rmse - 255.0).abs() < 1e-9);
+        assert!(m.flip_mean > 0.9, "FLIP {}", m.flip_mean);
+    }
+}
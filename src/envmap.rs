@@ -0,0 +1,452 @@
+use crate::light::{Light, LightSample};
+use crate::math::{luminance, Color, Point3, Vec3};
+use crate::ray::Ray;
+use std::f64::consts::PI;
+
+/// Piecewise-constant 1D distribution over [0, 1).
+struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>, // func.len() + 1 entries, cdf[0] = 0, cdf[n] = 1
+    integral: f64,
+}
+
+impl Distribution1D {
+    fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+        if integral == 0.0 {
+            // Degenerate (all black): fall back to uniform
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    // Returns (x in [0,1), pdf at x, bucket index)
+    fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.func.len();
+        let i = (self.cdf.partition_point(|&c| c <= u) - 1).min(n - 1);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let du = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+        (
+            ((i as f64 + du) / n as f64).min(1.0 - 1e-12),
+            self.pdf(i),
+            i,
+        )
+    }
+
+    fn pdf(&self, i: usize) -> f64 {
+        if self.integral == 0.0 {
+            1.0
+        } else {
+            self.func[i] / self.integral
+        }
+    }
+}
+
+/// Equirectangular (latitude-longitude) radiance map used as the background
+/// and as a light, importance-sampled by luminance.
+pub struct EnvMap {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<Color>, // row 0 is the top of the image (+Y)
+    pub rotation: f64,      // radians around +Y
+    pub intensity: f64,
+    marginal: Distribution1D,          // over rows (v)
+    conditionals: Vec<Distribution1D>, // over columns (u), one per row
+}
+
+impl EnvMap {
+    pub fn new(
+        width: usize,
+        height: usize,
+        texels: Vec<Color>,
+        rotation_deg: f64,
+        intensity: f64,
+    ) -> Self {
+        let mut conditionals = Vec::with_capacity(height);
+        let mut row_weights = Vec::with_capacity(height);
+        for y in 0..height {
+            // sin(theta) accounts for the squeezed area of rows near the poles
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            let row: Vec<f64> = texels[y * width..(y + 1) * width]
+                .iter()
+                .map(|c| luminance(*c) * sin_theta)
+                .collect();
+            let d = Distribution1D::new(row);
+            row_weights.push(d.integral);
+            conditionals.push(d);
+        }
+        Self {
+            width,
+            height,
+            texels,
+            rotation: rotation_deg.to_radians(),
+            intensity,
+            marginal: Distribution1D::new(row_weights),
+            conditionals,
+        }
+    }
+
+    /// Loads a Radiance `.hdr` (RGBE) or a `.pfm` file, picked by extension.
+    pub fn load(path: &str, rotation_deg: f64, intensity: f64) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        let lower = path.to_lowercase();
+        let (w, h, texels) = if lower.ends_with(".pfm") {
+            parse_pfm(&bytes)
+        } else {
+            parse_hdr(&bytes)
+        }
+        .map_err(|e| format!("{path}: {e}"))?;
+        Ok(Self::new(w, h, texels, rotation_deg, intensity))
+    }
+
+    // Direction -> (u, v) in [0,1)^2, v = 0 at +Y
+    fn dir_to_uv(&self, d: Vec3) -> (f64, f64) {
+        let d = d.unit();
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        let phi = (d.x.atan2(-d.z) - self.rotation).rem_euclid(2.0 * PI);
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn uv_to_dir(&self, u: f64, v: f64) -> Vec3 {
+        let theta = v * PI;
+        let phi = u * 2.0 * PI + self.rotation;
+        let s = theta.sin();
+        Vec3::new(s * phi.sin(), theta.cos(), -s * phi.cos())
+    }
+
+    fn texel(&self, u: f64, v: f64) -> Color {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.texels[y * self.width + x]
+    }
+
+    /// Radiance seen along direction `d`.
+    pub fn radiance(&self, d: Vec3) -> Color {
+        let (u, v) = self.dir_to_uv(d);
+        self.texel(u, v) * self.intensity
+    }
+
+    fn pdf_dir(&self, d: Vec3) -> f64 {
+        let (u, v) = self.dir_to_uv(d);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        let pdf_uv = self.marginal.pdf(y) * self.conditionals[y].pdf(x);
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+impl Light for EnvMap {
+    fn sample_li(&self, _p: Point3, u: (f64, f64)) -> Option<LightSample> {
+        let (v, pdf_v, row) = self.marginal.sample(u.0);
+        let (uu, pdf_u, _) = self.conditionals[row].sample(u.1);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return None;
+        }
+        let pdf = pdf_u * pdf_v / (2.0 * PI * PI * sin_theta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi: self.uv_to_dir(uu, v),
+            dist: f64::INFINITY,
+            li: self.texel(uu, v) * self.intensity,
+            pdf,
+        })
+    }
+
+    fn pdf_li(&self, r: &Ray, t_hit: f64) -> f64 {
+        if t_hit.is_finite() {
+            0.0
+        } else {
+            self.pdf_dir(r.direction)
+        }
+    }
+
+    fn le(&self, r: &Ray, t_hit: f64) -> Color {
+        if t_hit.is_finite() {
+            Color::new(0.0, 0.0, 0.0)
+        } else {
+            self.radiance(r.direction)
+        }
+    }
+}
+
+// Reads one whitespace-delimited header token starting at `*pos`
+fn next_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a str> {
+    while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    std::str::from_utf8(&bytes[start..*pos]).ok()
+}
+
+fn parse_pfm(bytes: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut pos = 0;
+    let channels = match next_token(bytes, &mut pos) {
+        Some("PF") => 3,
+        Some("Pf") => 1,
+        _ => return Err("not a PFM file".into()),
+    };
+    let w: usize = next_token(bytes, &mut pos)
+        .and_then(|t| t.parse().ok())
+        .ok_or("bad PFM width")?;
+    let h: usize = next_token(bytes, &mut pos)
+        .and_then(|t| t.parse().ok())
+        .ok_or("bad PFM height")?;
+    let scale: f64 = next_token(bytes, &mut pos)
+        .and_then(|t| t.parse().ok())
+        .ok_or("bad PFM scale")?;
+    pos += 1; // single whitespace byte before the raster
+    let little = scale < 0.0;
+    if w == 0 || h == 0 {
+        return Err("empty PFM image".into());
+    }
+    let end = w
+        .checked_mul(h)
+        .and_then(|n| n.checked_mul(channels * 4))
+        .and_then(|need| pos.checked_add(need));
+    if end.is_none_or(|end| bytes.len() < end) {
+        return Err("truncated PFM data".into());
+    }
+    let read = |i: usize| {
+        let b = [
+            bytes[pos + 4 * i],
+            bytes[pos + 4 * i + 1],
+            bytes[pos + 4 * i + 2],
+            bytes[pos + 4 * i + 3],
+        ];
+        if little {
+            f32::from_le_bytes(b) as f64
+        } else {
+            f32::from_be_bytes(b) as f64
+        }
+    };
+    let mut texels = vec![Color::new(0.0, 0.0, 0.0); w * h];
+    for y in 0..h {
+        // PFM rows run bottom to top
+        let dst = (h - 1 - y) * w;
+        for x in 0..w {
+            let i = (y * w + x) * channels;
+            texels[dst + x] = if channels == 3 {
+                Color::new(read(i), read(i + 1), read(i + 2))
+            } else {
+                let g = read(i);
+                Color::new(g, g, g)
+            };
+        }
+    }
+    Ok((w, h, texels))
+}
+
+fn parse_hdr(bytes: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    if !bytes.starts_with(b"#?") {
+        return Err("not a Radiance HDR file".into());
+    }
+    // Header lines end with an empty line; the resolution line follows.
+    let mut pos = 0;
+    loop {
+        let end = bytes[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or("truncated HDR header")?;
+        let line = &bytes[pos..pos + end];
+        pos += end + 1;
+        if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+            return Err("unsupported HDR pixel format".into());
+        }
+        if line.is_empty() {
+            break;
+        }
+    }
+    let (ny, h, nx, w) = (
+        next_token(bytes, &mut pos),
+        next_token(bytes, &mut pos),
+        next_token(bytes, &mut pos),
+        next_token(bytes, &mut pos),
+    );
+    if ny != Some("-Y") || nx != Some("+X") {
+        return Err("only -Y h +X w HDR orientation is supported".into());
+    }
+    let h: usize = h.and_then(|t| t.parse().ok()).ok_or("bad HDR height")?;
+    let w: usize = w.and_then(|t| t.parse().ok()).ok_or("bad HDR width")?;
+    pos += 1;
+    if w == 0 || h == 0 {
+        return Err("empty HDR image".into());
+    }
+    // A run-length byte pair covers at most 127 pixels of one channel, so even
+    // a fully compressed image needs 8 bytes per 127 pixels
+    let min_len = w.checked_mul(h).map(|n| n.div_ceil(127) * 8);
+    if min_len.is_none_or(|n| bytes.len().saturating_sub(pos) < n) {
+        return Err("truncated HDR data".into());
+    }
+
+    let mut texels = Vec::with_capacity(w * h);
+    let mut scan = vec![[0u8; 4]; w];
+    let byte = |pos: &mut usize| -> Result<u8, String> {
+        let b = *bytes.get(*pos).ok_or("truncated HDR data")?;
+        *pos += 1;
+        Ok(b)
+    };
+    for _ in 0..h {
+        let rle = (8..=0x7fff).contains(&w)
+            && bytes.get(pos..pos + 4).is_some_and(|b| {
+                b[0] == 2
+                    && b[1] == 2
+                    && b[2] & 0x80 == 0
+                    && ((b[2] as usize) << 8 | b[3] as usize) == w
+            });
+        if rle {
+            pos += 4;
+            for ch in 0..4 {
+                let mut x = 0;
+                while x < w {
+                    let count = byte(&mut pos)? as usize;
+                    if count > 128 {
+                        let run = count - 128;
+                        let v = byte(&mut pos)?;
+                        if x + run > w {
+                            return Err("bad HDR run length".into());
+                        }
+                        for px in scan.iter_mut().skip(x).take(run) {
+                            px[ch] = v;
+                        }
+                        x += run;
+                    } else {
+                        if count == 0 || x + count > w {
+                            return Err("bad HDR run length".into());
+                        }
+                        for px in scan.iter_mut().skip(x).take(count) {
+                            px[ch] = byte(&mut pos)?;
+                        }
+                        x += count;
+                    }
+                }
+            }
+        } else {
+            for px in scan.iter_mut() {
+                for c in px.iter_mut() {
+                    *c = byte(&mut pos)?;
+                }
+            }
+        }
+        texels.extend(scan.iter().map(|&[r, g, b, e]| rgbe_to_color(r, g, b, e)));
+    }
+    Ok((w, h, texels))
+}
+
+fn rgbe_to_color(r: u8, g: u8, b: u8, e: u8) -> Color {
+    if e == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(e as i32 - 136);
+    Color::new(
+        (r as f64 + 0.5) * f,
+        (g as f64 + 0.5) * f,
+        (b as f64 + 0.5) * f,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pfm(header: &str, floats: &[f32]) -> Vec<u8> {
+        let mut bytes = header.as_bytes().to_vec();
+        for f in floats {
+            bytes.extend_from_slice(&f.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn rgb(c: Color) -> (f64, f64, f64) {
+        (c.x, c.y, c.z)
+    }
+
+    // RGBE mantissas are read from the middle of their bucket
+    fn near(c: Color, (r, g, b): (f64, f64, f64)) -> bool {
+        (c.x - r).abs() < 0.01 && (c.y - g).abs() < 0.01 && (c.z - b).abs() < 0.01
+    }
+
+    #[test]
+    fn pfm_rows_are_flipped_and_sizes_checked() {
+        let (w, h, texels) = parse_pfm(&pfm("Pf\n1 2\n-1.0\n", &[0.25, 0.5])).unwrap();
+        assert_eq!((w, h), (1, 2));
+        // The first row in the file is the bottom one
+        assert_eq!(rgb(texels[0]), (0.5, 0.5, 0.5));
+        assert_eq!(rgb(texels[1]), (0.25, 0.25, 0.25));
+
+        let err = |bytes: &[u8]| parse_pfm(bytes).err().unwrap();
+        assert_eq!(
+            err(&pfm("PF\n2 2\n-1.0\n", &[1.0; 11])),
+            "truncated PFM data"
+        );
+        assert_eq!(err(&pfm("PF\n0 2\n-1.0\n", &[])), "empty PFM image");
+        assert_eq!(err(&pfm("PF\n-2 2\n-1.0\n", &[1.0; 12])), "bad PFM width");
+        assert_eq!(err(&pfm("PF\n2.5 2\n-1.0\n", &[1.0; 12])), "bad PFM width");
+        let huge = format!("PF\n{} {}\n-1.0\n", usize::MAX / 2, 2);
+        assert_eq!(err(&pfm(&huge, &[1.0; 12])), "truncated PFM data");
+        let wide = format!("PF\n{} 1\n-1.0\n", usize::MAX / 12);
+        assert_eq!(err(&pfm(&wide, &[1.0; 12])), "truncated PFM data");
+    }
+
+    #[test]
+    fn hdr_flat_and_run_length_scanlines_decode() {
+        let header = |w: usize, h: usize| {
+            format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {h} +X {w}\n").into_bytes()
+        };
+        // Flat 2x1: mantissa 128 with exponent 129 is 1.0
+        let mut flat = header(2, 1);
+        flat.extend_from_slice(&[128, 128, 128, 129, 0, 0, 0, 0]);
+        let (w, h, texels) = parse_hdr(&flat).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert!(near(texels[0], (1.0, 1.0, 1.0)));
+        assert_eq!(rgb(texels[1]), (0.0, 0.0, 0.0));
+
+        // One run-length scanline of 8 equal pixels
+        let mut rle = header(8, 1);
+        rle.extend_from_slice(&[2, 2, 0, 8]);
+        for v in [128, 64, 0, 129] {
+            rle.extend_from_slice(&[128 + 8, v]);
+        }
+        let (_, _, texels) = parse_hdr(&rle).unwrap();
+        assert_eq!(texels.len(), 8);
+        assert!(texels.iter().all(|&c| near(c, (1.0, 0.5, 0.0))));
+
+        let err = |bytes: &[u8]| parse_hdr(bytes).err().unwrap();
+        assert_eq!(err(&flat[..flat.len() - 1]), "truncated HDR data");
+        assert_eq!(err(&header(0, 1)), "empty HDR image");
+        let mut huge = header(usize::MAX / 2, 4);
+        huge.extend_from_slice(&[0; 64]);
+        assert_eq!(err(&huge), "truncated HDR data");
+        let mut long_run = header(8, 1);
+        long_run.extend_from_slice(&[2, 2, 0, 8, 128 + 9, 0, 0, 0]);
+        assert_eq!(err(&long_run), "bad HDR run length");
+    }
+}
@@ -0,0 +1,149 @@
+use crate::checkpoint::{put_f64, Reader};
+use crate::filter::Filter;
+use crate::math::Color;
+
+/// Half-open pixel rectangle `[x0, x1) x [y0, y1)`, with rows counted from
+/// the bottom like the film.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Rect {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    pub fn full(width: usize, height: usize) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn contains(&self, i: usize, j: usize) -> bool {
+        (self.x0..self.x1).contains(&i) && (self.y0..self.y1).contains(&j)
+    }
+
+    /// Grown by `m` pixels on every side, clipped to a `width` x `height` frame.
+    pub fn grow(&self, m: usize, width: usize, height: usize) -> Self {
+        Self::new(
+            self.x0.saturating_sub(m),
+            self.y0.saturating_sub(m),
+            (self.x1 + m).min(width),
+            (self.y1 + m).min(height),
+        )
+    }
+
+    /// Every pixel, bottom row first, left to right.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x0, x1) = (self.x0, self.x1);
+        (self.y0..self.y1).flat_map(move |j| (x0..x1).map(move |i| (i, j)))
+    }
+}
+
+/// Weighted framebuffer. Each sample is splatted into every pixel whose
+/// center lies within the filter radius; a pixel's value is the weighted sum
+/// of its samples divided by the sum of their weights.
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pub filter: Filter,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        Self {
+            width,
+            height,
+            filter,
+            sums: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    /// Adds a sample at continuous film position `(x, y)`, where pixel `(i, j)`
+    /// covers `[i, i+1) x [j, j+1)` and `j` counts rows from the bottom.
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        let r = self.filter.radius;
+        // Pixels whose centers (i + 0.5, j + 0.5) are within the radius
+        let x0 = (x - 0.5 - r).ceil().max(0.0) as usize;
+        let x1 = ((x - 0.5 + r).floor() as i64).min(self.width as i64 - 1);
+        let y0 = (y - 0.5 - r).ceil().max(0.0) as usize;
+        let y1 = ((y - 0.5 + r).floor() as i64).min(self.height as i64 - 1);
+        for j in y0 as i64..=y1 {
+            for i in x0 as i64..=x1 {
+                let w = self.filter.eval(x - (i as f64 + 0.5), y - (j as f64 + 0.5));
+                if w != 0.0 {
+                    let idx = j as usize * self.width + i as usize;
+                    self.sums[idx] += color * w;
+                    self.weights[idx] += w;
+                }
+            }
+        }
+    }
+
+    /// Filtered value of pixel `(i, j)`, black if no sample reached it.
+    /// Negative filter lobes can undershoot, so the result is clamped at zero.
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        let idx = j * self.width + i;
+        let w = self.weights[idx];
+        if w <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let c = self.sums[idx] / w;
+        Color::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0))
+    }
+
+    /// The pixels that samples taken inside `rect` can reach, given the
+    /// filter radius, clipped to the film.
+    pub fn splat_rect(&self, rect: Rect) -> Rect {
+        let m = (self.filter.radius + 0.5).ceil() as usize;
+        rect.grow(m, self.width, self.height)
+    }
+
+    pub const SAVED_PIXEL_BYTES: usize = 32;
+
+    /// Appends the accumulated sums and weights of `rect`.
+    pub fn save_rect(&self, rect: Rect, out: &mut Vec<u8>) {
+        for (i, j) in rect.pixels() {
+            let idx = j * self.width + i;
+            let c = self.sums[idx];
+            put_f64(out, c.x);
+            put_f64(out, c.y);
+            put_f64(out, c.z);
+            put_f64(out, self.weights[idx]);
+        }
+    }
+
+    /// Adds what `save_rect` wrote for the same `rect` onto this film.
+    pub fn add_rect(&mut self, rect: Rect, r: &mut Reader) -> Result<(), String> {
+        if rect.x1 > self.width || rect.y1 > self.height {
+            return Err("rectangle outside the film".into());
+        }
+        for (i, j) in rect.pixels() {
+            let idx = j * self.width + i;
+            self.sums[idx] += Color::new(r.f64()?, r.f64()?, r.f64()?);
+            self.weights[idx] += r.f64()?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_rect(&mut self, rect: Rect) {
+        for (i, j) in rect.pixels() {
+            let idx = j * self.width + i;
+            self.sums[idx] = Color::new(0.0, 0.0, 0.0);
+            self.weights[idx] = 0.0;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+/// Separable pixel reconstruction filter. `radius` is in pixels; samples
+/// farther than that from a pixel center don't contribute to it.
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub radius: f64,
+}
+
+impl FilterKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "box" => Some(FilterKind::Box),
+            "tent" | "triangle" => Some(FilterKind::Tent),
+            "gaussian" => Some(FilterKind::Gaussian),
+            "mitchell" => Some(FilterKind::Mitchell),
+            "lanczos" => Some(FilterKind::Lanczos),
+            _ => None,
+        }
+    }
+
+    pub fn default_radius(self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell | FilterKind::Lanczos => 2.0,
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        Self { kind, radius }
+    }
+
+    /// Weight of a sample at offset `(dx, dy)` pixels from a pixel center.
+    /// Mitchell and Lanczos have small negative lobes.
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    fn eval_1d(&self, x: f64) -> f64 {
+        let r = self.radius;
+        let x = x.abs();
+        if x >= r {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x / r,
+            FilterKind::Gaussian => {
+                // sigma = r / 3, shifted so the weight reaches zero at the radius
+                let alpha = 0.5 / (r / 3.0).powi(2);
+                (-alpha * x * x).exp() - (-alpha * r * r).exp()
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x / r),
+            FilterKind::Lanczos => {
+                // Two-lobe windowed sinc stretched over the radius
+                let t = 2.0 * x / r;
+                sinc(t) * sinc(t / 2.0)
+            }
+        }
+    }
+}
+
+// Mitchell-Netravali cubic with B = C = 1/3, defined on [0, 2)
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    let (x2, x3) = (x * x, x * x * x);
+    if x < 1.0 {
+        ((12.0 - 9.0 * B - 6.0 * C) * x3 + (-18.0 + 12.0 * B + 6.0 * C) * x2 + (6.0 - 2.0 * B))
+            / 6.0
+    } else {
+        ((-B - 6.0 * C) * x3
+            + (6.0 * B + 30.0 * C) * x2
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C))
+            / 6.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::Film;
+    use crate::math::Color;
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn weights_peak_at_the_center_and_vanish_at_the_radius() {
+        for kind in KINDS {
+            let f = Filter::new(kind, kind.default_radius());
+            let r = f.radius;
+            assert!(f.eval(0.0, 0.0) > 0.0, "{kind:?}");
+            assert!(f.eval(0.3, -0.2) <= f.eval(0.0, 0.0), "{kind:?}");
+            assert_eq!(f.eval(0.3, -0.2), f.eval(-0.3, 0.2), "{kind:?}");
+            assert_eq!(f.eval(r, 0.0), 0.0, "{kind:?}");
+            assert_eq!(f.eval(0.0, r + 0.1), 0.0, "{kind:?}");
+            // All but the box fall to zero continuously; the Gaussian is
+            // shifted down to get there
+            if kind != FilterKind::Box {
+                assert!(f.eval(r - 1e-6, 0.0).abs() < 1e-3, "{kind:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn film_weights_normalize_every_filter() {
+        // Jittered samples of one color: whatever the filter's weights add up
+        // to, including negative lobes, interior pixels come out that color
+        let c = Color::new(0.2, 0.5, 0.8);
+        for kind in KINDS {
+            let mut film = Film::new(8, 8, Filter::new(kind, kind.default_radius()));
+            for k in 0..64 * 64 {
+                let (sx, sy) = (k % 64, k / 64);
+                let jitter = (k * 7919 % 97) as f64 / 97.0;
+                film.add_sample(
+                    (sx as f64 + jitter) / 8.0,
+                    (sy as f64 + 1.0 - jitter) / 8.0,
+                    c,
+                );
+            }
+            for (i, j) in [(3, 3), (4, 2), (5, 5)] {
+                let p = film.pixel(i, j);
+                let err = (p.x - c.x).abs() + (p.y - c.y).abs() + (p.z - c.z).abs();
+                assert!(err < 1e-9, "{kind:?}: pixel ({i}, {j}) is {p:?}");
+            }
+        }
+    }
+}
@@ -1,23 +1,30 @@
-use crate::math::{Color, Point3, Vec3};
+use crate::material::Material;
+use crate::math::{Point3, Vec3};
 use crate::ray::Ray;
+use std::sync::Arc;
 
-#[derive(Clone, Copy, Debug)]
-pub struct HitRecord {
+#[derive(Clone, Copy)]
+pub struct HitRecord<'a> {
     pub p: Point3,
-    pub normal: Vec3,
+    pub normal: Vec3, // shading normal, possibly perturbed by normal/bump maps
+    pub geometric_normal: Vec3, // true surface normal, on the same side as `normal`
     pub t: f64,
-    pub albedo: Color,
-    pub reflectivity: f64,
+    pub front_face: bool,
+    pub material: &'a Material,
+    // Surface parameterization and its tangents (dp/du, dp/dv); zero if unset
+    pub u: f64,
+    pub v: f64,
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
 
-impl HitRecord {
+impl<'a> HitRecord<'a> {
     pub fn with_face_normal(
         r: &Ray,
         p: Point3,
         outward_normal: Vec3,
         t: f64,
-        albedo: Color,
-        reflectivity: f64,
+        material: &'a Material,
     ) -> Self {
         let front = Vec3::dot(r.direction, outward_normal) < 0.0;
         let normal = if front {
@@ -28,15 +35,44 @@ impl HitRecord {
         Self {
             p,
             normal,
+            geometric_normal: normal,
             t,
-            albedo,
-            reflectivity,
+            front_face: front,
+            material,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
         }
     }
+
+    pub fn with_uv(mut self, u: f64, v: f64, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.u = u;
+        self.v = v;
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
 }
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+}
+
+/// A hittable with a finite surface that can be sampled uniformly by area,
+/// which is what an emissive object needs to act as a light.
+pub trait Shape: Hittable {
+    fn area(&self) -> f64;
+    /// Uniformly distributed point on the surface and its outward normal.
+    fn sample_surface(&self, u: (f64, f64)) -> (Point3, Vec3);
+    fn material(&self) -> &Material;
+}
+
+// Lets the same object live in the world and in the light list.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
 }
 
 pub struct HittableList {
@@ -55,7 +91,7 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut closest_so_far = t_max;
         let mut hit_any: Option<HitRecord> = None;
 
@@ -68,3 +104,101 @@ impl Hittable for HittableList {
         hit_any
     }
 }
+
+// Shared pieces of the primitives' property tests
+#[cfg(test)]
+pub mod testing {
+    use super::HitRecord;
+    use crate::math::{Point3, Vec3};
+    use crate::ray::Ray;
+    use proptest::prelude::*;
+
+    pub fn point(extent: f64) -> impl Strategy<Value = Point3> {
+        (-extent..extent, -extent..extent, -extent..extent)
+            .prop_map(|(x, y, z)| Point3::new(x, y, z))
+    }
+
+    /// Unit vector, uniform over the sphere.
+    pub fn unit() -> impl Strategy<Value = Vec3> {
+        point(1.0)
+            .prop_filter("near zero", |v| v.length_squared() > 1e-4)
+            .prop_map(|v| v.unit())
+    }
+
+    /// Point inside the unit ball.
+    pub fn in_ball() -> impl Strategy<Value = Vec3> {
+        point(1.0).prop_filter("outside the ball", |v| v.length_squared() < 1.0)
+    }
+
+    /// Ray direction of any length, since hit routines shouldn't assume unit
+    /// directions.
+    pub fn direction() -> impl Strategy<Value = Vec3> {
+        (unit(), 0.01..100.0).prop_map(|(d, len)| d * len)
+    }
+
+    /// Invariants of every hit: `t` within range, `p` on the ray, a unit normal
+    /// facing against the ray, and finite UVs with independent tangents lying
+    /// in the surface.
+    pub fn check_hit(
+        rec: &HitRecord,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Result<(), TestCaseError> {
+        prop_assert!(
+            rec.t >= t_min && rec.t <= t_max,
+            "t = {} outside [{}, {}]",
+            rec.t,
+            t_min,
+            t_max
+        );
+        let on_ray = r.at(rec.t);
+        prop_assert!(
+            (rec.p - on_ray).length() <= 1e-9 * (1.0 + on_ray.length()),
+            "p = {:?} is not the ray at t = {}",
+            rec.p,
+            rec.t
+        );
+        prop_assert!(
+            (rec.normal.length() - 1.0).abs() < 1e-9,
+            "normal {:?} isn't unit length",
+            rec.normal
+        );
+        prop_assert!(
+            Vec3::dot(rec.normal, r.direction) <= 0.0,
+            "normal {:?} faces along the ray {:?}",
+            rec.normal,
+            r.direction
+        );
+        prop_assert!(
+            rec.u.is_finite() && rec.v.is_finite(),
+            "uv = ({}, {})",
+            rec.u,
+            rec.v
+        );
+        for (name, d) in [("dpdu", rec.dpdu), ("dpdv", rec.dpdv)] {
+            prop_assert!(
+                d.length() > 0.0 && Vec3::dot(d, rec.normal).abs() <= 1e-6 * d.length(),
+                "{name} = {:?} isn't tangent to the normal {:?}",
+                d,
+                rec.normal
+            );
+        }
+        prop_assert!(
+            Vec3::cross(rec.dpdu, rec.dpdv).length() > 1e-6 * rec.dpdu.length() * rec.dpdv.length(),
+            "dpdu {:?} and dpdv {:?} are parallel",
+            rec.dpdu,
+            rec.dpdv
+        );
+        Ok(())
+    }
+
+    /// The surface normal pointing out of the solid, whichever side was hit.
+    pub fn outward(rec: &HitRecord) -> Vec3 {
+        if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        }
+    }
+}
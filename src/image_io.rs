@@ -0,0 +1,177 @@
+use crate::math::Color;
+
+/// 8-bit RGB image decoded to [0,1] floats. Values are still sRGB-encoded.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>, // row-major, row 0 at the top
+}
+
+/// Loads a PNG or a binary/ASCII PPM (P6/P3), picked by file contents.
+pub fn load_image(path: &str) -> Result<Image, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+    if bytes.starts_with(b"\x89PNG") {
+        decode_png(&bytes)
+    } else if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+        decode_ppm(&bytes)
+    } else {
+        Err("unsupported image format (expected PNG or PPM)".into())
+    }
+    .map_err(|e| format!("{path}: {e}"))
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Expand palettes and low bit depths, strip 16-bit down to 8
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+    let (w, h) = (info.width as usize, info.height as usize);
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("unexpanded palette".into()),
+    };
+    let mut pixels = Vec::with_capacity(w * h);
+    for y in 0..h {
+        let row = &buf[y * info.line_size..];
+        for x in 0..w {
+            let px = &row[x * channels..];
+            let c = if channels < 3 {
+                let g = px[0] as f64 / 255.0;
+                Color::new(g, g, g)
+            } else {
+                Color::new(
+                    px[0] as f64 / 255.0,
+                    px[1] as f64 / 255.0,
+                    px[2] as f64 / 255.0,
+                )
+            };
+            pixels.push(c);
+        }
+    }
+    Ok(Image {
+        width: w,
+        height: h,
+        pixels,
+    })
+}
+
+fn decode_ppm(bytes: &[u8]) -> Result<Image, String> {
+    // Header: magic, width, height, maxval; '#' comments allowed between tokens
+    let mut pos = 0;
+    let mut token = || -> Option<&[u8]> {
+        loop {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        (pos > start).then(|| &bytes[start..pos])
+    };
+    let magic = token().ok_or("missing PPM magic")?.to_vec();
+    let mut num = || -> Result<usize, String> {
+        token()
+            .and_then(|t| std::str::from_utf8(t).ok()?.parse().ok())
+            .ok_or_else(|| "bad PPM header".to_string())
+    };
+    let (w, h, maxval) = (num()?, num()?, num()?);
+    if w == 0 || h == 0 || maxval == 0 || maxval > 65535 {
+        return Err("bad PPM header".into());
+    }
+    let scale = 1.0 / maxval as f64;
+    let n = w.checked_mul(h).ok_or("PPM image too large")?;
+    let mut pixels;
+    if magic == b"P6" {
+        let data = &bytes[(pos + 1).min(bytes.len())..];
+        let bpc = if maxval < 256 { 1 } else { 2 };
+        let need = n.checked_mul(3 * bpc).ok_or("PPM image too large")?;
+        if data.len() < need {
+            return Err("truncated PPM data".into());
+        }
+        pixels = Vec::with_capacity(n);
+        let sample = |i: usize| {
+            if bpc == 1 {
+                data[i] as f64
+            } else {
+                u16::from_be_bytes([data[2 * i], data[2 * i + 1]]) as f64
+            }
+        };
+        for i in 0..n {
+            pixels.push(Color::new(sample(3 * i), sample(3 * i + 1), sample(3 * i + 2)) * scale);
+        }
+    } else {
+        // Every pixel takes at least six bytes of text, so don't trust the
+        // header beyond that
+        pixels = Vec::with_capacity(n.min(bytes.len() / 6));
+        let mut next = || num().map(|v| v as f64 * scale);
+        for _ in 0..n {
+            pixels.push(Color::new(next()?, next()?, next()?));
+        }
+    }
+    Ok(Image {
+        width: w,
+        height: h,
+        pixels,
+    })
+}
+
+/// sRGB opto-electronic transfer function (linear [0,1] -> encoded).
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// sRGB electro-optical transfer function (encoded [0,1] -> linear).
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ppm_sizes_are_checked_before_reading() {
+        let p6 = |header: &str, data: &[u8]| {
+            let mut bytes = header.as_bytes().to_vec();
+            bytes.extend_from_slice(data);
+            decode_ppm(&bytes)
+        };
+        let img = p6("P6\n2 1\n255\n", &[255, 0, 0, 0, 0, 255]).unwrap();
+        assert_eq!((img.width, img.height), (2, 1));
+        let px = img.pixels[1];
+        assert_eq!((px.x, px.y, px.z), (0.0, 0.0, 1.0));
+        assert_eq!(
+            p6("P6\n2 1\n255\n", &[0; 5]).err().unwrap(),
+            "truncated PPM data"
+        );
+        let huge = format!("P6\n{} {}\n65535\n", usize::MAX / 2, 3);
+        assert_eq!(p6(&huge, &[0; 6]).err().unwrap(), "PPM image too large");
+        let wide = format!("P6\n{} 1\n65535\n", usize::MAX / 4);
+        assert_eq!(p6(&wide, &[0; 6]).err().unwrap(), "PPM image too large");
+        // ASCII data claiming far more pixels than it holds
+        let p3 = format!("P3\n{} 1000\n255\n1 2 3", 1usize << 40);
+        assert!(decode_ppm(p3.as_bytes()).is_err());
+    }
+}
@@ -1,4 +1,52 @@
-use crate::math::{Color, Point3};
+use crate::hittable::{HitRecord, Shape};
+use crate::math::{orthonormal_basis, Color, Point3, Vec3};
+use crate::ray::Ray;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct LightSample {
+    pub wi: Vec3,  // unit direction from the shading point towards the light
+    pub dist: f64, // distance to the sampled point (INFINITY for lights at infinity)
+    pub li: Color, // incident radiance along wi
+    pub pdf: f64,  // solid-angle density (1.0 for delta lights)
+}
+
+pub trait Light: Send + Sync {
+    /// Picks a direction from `p` towards the light using the random pair `u`.
+    fn sample_li(&self, p: Point3, u: (f64, f64)) -> Option<LightSample>;
+
+    /// Density with which `sample_li` would have produced `r.direction` from
+    /// `r.origin`, given that the closest surface along `r` is at `t_hit`.
+    fn pdf_li(&self, _r: &Ray, _t_hit: f64) -> f64 {
+        0.0
+    }
+
+    /// Radiance this light contributes along `r` when the closest surface is at `t_hit`.
+    /// Delta lights can never be hit by a ray and return black.
+    fn le(&self, _r: &Ray, _t_hit: f64) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+}
+
+// Lets a light be shared, e.g. an environment map that is also the background.
+impl<T: Light + ?Sized> Light for Arc<T> {
+    fn sample_li(&self, p: Point3, u: (f64, f64)) -> Option<LightSample> {
+        (**self).sample_li(p, u)
+    }
+    fn pdf_li(&self, r: &Ray, t_hit: f64) -> f64 {
+        (**self).pdf_li(r, t_hit)
+    }
+    fn le(&self, r: &Ray, t_hit: f64) -> Color {
+        (**self).le(r, t_hit)
+    }
+    fn is_delta(&self) -> bool {
+        (**self).is_delta()
+    }
+}
 
 pub struct PointLight {
     pub position: Point3,
@@ -6,7 +54,6 @@ pub struct PointLight {
 }
 
 impl PointLight {
-    // ⬅ change signature to take Point3 directly
     pub fn new(position: Point3, intensity: Color) -> Self {
         Self {
             position,
@@ -14,3 +61,266 @@ impl PointLight {
         }
     }
 }
+
+impl Light for PointLight {
+    fn sample_li(&self, p: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let dist = to_light.length();
+        if dist == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi: to_light / dist,
+            dist,
+            // No falloff, and scaled by PI so a white Lambertian facing the
+            // light reads `intensity` (the legacy shading convention).
+            li: self.intensity * PI,
+            pdf: 1.0,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// Point light restricted to a cone, fading smoothly from the inner to the outer angle.
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vec3, // unit axis of the cone, pointing away from the light
+    pub intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        inner_deg: f64,
+        outer_deg: f64,
+        intensity: Color,
+    ) -> Self {
+        let outer = outer_deg.clamp(0.0, 180.0);
+        let inner = inner_deg.clamp(0.0, outer);
+        Self {
+            position,
+            direction: direction.unit(),
+            intensity,
+            cos_inner: inner.to_radians().cos(),
+            cos_outer: outer.to_radians().cos(),
+        }
+    }
+
+    // 1 inside the inner cone, 0 outside the outer cone, smoothstep between
+    fn falloff(&self, from_light: Vec3) -> f64 {
+        let cos = Vec3::dot(from_light, self.direction);
+        if cos >= self.cos_inner {
+            return 1.0;
+        }
+        if cos <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let dist = to_light.length();
+        if dist == 0.0 {
+            return None;
+        }
+        let wi = to_light / dist;
+        let falloff = self.falloff(-wi);
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi,
+            dist,
+            // Same convention as PointLight
+            li: self.intensity * (PI * falloff),
+            pdf: 1.0,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// Light at infinity arriving along a single direction, so all shadow rays are parallel.
+pub struct DirectionalLight {
+    pub direction: Vec3, // unit direction the light travels in
+    pub intensity: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, intensity: Color) -> Self {
+        Self {
+            direction: direction.unit(),
+            intensity,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        Some(LightSample {
+            wi: -self.direction,
+            dist: f64::INFINITY,
+            li: self.intensity * PI,
+            pdf: 1.0,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+/// Any emissive shape used as a light, sampled uniformly by area.
+pub struct AreaLight {
+    pub shape: Arc<dyn Shape>,
+}
+
+impl AreaLight {
+    pub fn new(shape: Arc<dyn Shape>) -> Self {
+        Self { shape }
+    }
+
+    // The shape's own hit along `r`, if it is the closest surface at `t_hit`
+    fn visible_hit(&self, r: &Ray, t_hit: f64) -> Option<HitRecord<'_>> {
+        let rec = self.shape.hit(r, 0.0, f64::INFINITY)?;
+        if (rec.t - t_hit).abs() <= 1e-6 * rec.t.max(1.0) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn sample_li(&self, p: Point3, u: (f64, f64)) -> Option<LightSample> {
+        let (q, n) = self.shape.sample_surface(u);
+        let to_light = q - p;
+        let dist2 = to_light.length_squared();
+        if dist2 == 0.0 {
+            return None;
+        }
+        let dist = dist2.sqrt();
+        let wi = to_light / dist;
+        let cos_l = Vec3::dot(n, -wi);
+        if cos_l.abs() < 1e-8 {
+            return None;
+        }
+        let li = self.shape.material().emitted(cos_l > 0.0);
+        if li.x <= 0.0 && li.y <= 0.0 && li.z <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi,
+            dist,
+            li,
+            pdf: dist2 / (cos_l.abs() * self.shape.area()),
+        })
+    }
+
+    fn pdf_li(&self, r: &Ray, t_hit: f64) -> f64 {
+        match self.visible_hit(r, t_hit) {
+            Some(rec) => {
+                let dist2 = (rec.p - r.origin).length_squared();
+                let cos_l = Vec3::dot(rec.normal, r.direction.unit()).abs();
+                if cos_l < 1e-8 {
+                    0.0
+                } else {
+                    dist2 / (cos_l * self.shape.area())
+                }
+            }
+            None => 0.0,
+        }
+    }
+
+    fn le(&self, r: &Ray, t_hit: f64) -> Color {
+        match self.visible_hit(r, t_hit) {
+            Some(rec) => rec.material.emitted(rec.front_face),
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Sun-like light at infinity covering a small cone of directions, which
+/// gives soft shadow edges.
+pub struct SunLight {
+    pub direction: Vec3, // unit vector towards the sun
+    pub radiance: Color,
+    cos_max: f64, // cosine of the angular radius
+}
+
+impl SunLight {
+    /// `intensity` follows the point light convention: a white Lambertian
+    /// facing the sun reads `intensity`.
+    pub fn new(direction: Vec3, intensity: Color, angular_diameter_deg: f64) -> Self {
+        let half = (angular_diameter_deg.max(1e-3) * 0.5).to_radians();
+        let cos_max = half.cos();
+        let solid_angle = 2.0 * PI * (1.0 - cos_max);
+        Self {
+            direction: direction.unit(),
+            radiance: intensity * (PI / solid_angle),
+            cos_max,
+        }
+    }
+
+    fn in_cone(&self, d: Vec3) -> bool {
+        Vec3::dot(d.unit(), self.direction) >= self.cos_max
+    }
+
+    /// Radiance of the visible disc along `d` (black outside it).
+    pub fn disc_radiance(&self, d: Vec3) -> Color {
+        if self.in_cone(d) {
+            self.radiance
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn pdf(&self) -> f64 {
+        1.0 / (2.0 * PI * (1.0 - self.cos_max))
+    }
+}
+
+impl Light for SunLight {
+    fn sample_li(&self, _p: Point3, u: (f64, f64)) -> Option<LightSample> {
+        // Uniform direction inside the cone
+        let cos_t = 1.0 - u.0 * (1.0 - self.cos_max);
+        let sin_t = (1.0 - cos_t * cos_t).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let (s, t) = orthonormal_basis(self.direction);
+        let wi = s * (sin_t * phi.cos()) + t * (sin_t * phi.sin()) + self.direction * cos_t;
+        Some(LightSample {
+            wi,
+            dist: f64::INFINITY,
+            li: self.radiance,
+            pdf: self.pdf(),
+        })
+    }
+
+    fn pdf_li(&self, r: &Ray, t_hit: f64) -> f64 {
+        if t_hit.is_infinite() && self.in_cone(r.direction) {
+            self.pdf()
+        } else {
+            0.0
+        }
+    }
+
+    fn le(&self, r: &Ray, t_hit: f64) -> Color {
+        if t_hit.is_infinite() {
+            self.disc_radiance(r.direction)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+}
@@ -1,87 +1,279 @@
+mod adaptive;
 mod camera;
+mod checkpoint;
 mod cube;
 mod cylinder;
+mod denoise;
+mod diff;
+mod envmap;
+mod film;
+mod filter;
 mod hittable;
+mod image_io;
 mod light;
+mod material;
 mod math;
+mod merge;
+mod mesh;
+mod net;
 mod plane;
+mod principled;
+mod progress;
 mod ray;
+mod sampler;
+mod sky;
 mod sphere;
+mod stats;
+mod texture;
+mod tonemap;
+mod triangle;
 
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Instant;
 
+use adaptive::{AdaptiveOpts, PixelStats};
 use camera::Camera;
+use checkpoint::{put_u32, put_u64, Reader};
 use cube::Cube;
 use cylinder::Cylinder;
-use hittable::{Hittable, HittableList};
-use light::PointLight;
-use math::reflect;
+use denoise::{DenoiseOpts, Features};
+use envmap::EnvMap;
+use film::{Film, Rect};
+use filter::{Filter, FilterKind};
+use hittable::HitRecord;
+use hittable::{Hittable, HittableList, Shape};
+use light::{AreaLight, DirectionalLight, Light, PointLight, SpotLight, SunLight};
+use material::{Bxdf, Material, Phong};
+use math::{power_heuristic, reflect};
 use math::{Color, Point3, Vec3};
+use mesh::Mesh;
 use plane::Plane;
+use principled::Principled;
+use progress::Progress;
 use ray::Ray;
+use sampler::{Sampler, SamplerKind};
+use sky::{Background, PhysicalSky, PreethamSky};
 use sphere::Sphere;
+use stats::RayKind;
+use texture::{Checker, ImageTexture, NoiseTexture, SolidColor, Texture, WrapMode};
+use tonemap::{DisplayTransform, ToneMap, Transfer};
+use triangle::Triangle;
 
-fn write_color(w: &mut BufWriter<File>, pixel_color: Color, samples_per_pixel: i32) {
-    let scale = 1.0 / samples_per_pixel as f64;
-    let mut r = pixel_color.x * scale;
-    let mut g = pixel_color.y * scale;
-    let mut b = pixel_color.z * scale;
-
-    // gamma 2.0
-    r = r.sqrt();
-    g = g.sqrt();
-    b = b.sqrt();
+fn write_color(w: &mut BufWriter<File>, pixel_color: Color, display: &DisplayTransform) {
+    let c = display.apply(pixel_color);
+    let (r, g, b) = (c.x, c.y, c.z);
 
     let to_byte = |c: f64| (c.clamp(0.0, 0.999) * 256.0) as i32;
     writeln!(w, "{} {} {}", to_byte(r), to_byte(g), to_byte(b)).unwrap();
 }
 
-fn shade_lambert_with_shadow(
-    hit_color: Color,
-    normal: Vec3,
-    p: Point3,
-    light: &PointLight,
+// `pixels` rows run bottom to top. Written to a temporary file and renamed, so
+// a viewer reloading the output never sees a half-written image.
+fn write_ppm(
+    path: &str,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+    display: &DisplayTransform,
+) {
+    let tmp = format!("{path}.tmp");
+    let file = File::create(&tmp).expect("Failed to create file");
+    let mut w = BufWriter::new(file);
+    writeln!(w, "P3").unwrap();
+    writeln!(w, "{} {}", width, height).unwrap();
+    writeln!(w, "255").unwrap();
+    for j in (0..height).rev() {
+        for i in 0..width {
+            write_color(&mut w, pixels[j * width + i], display);
+        }
+    }
+    w.flush().unwrap();
+    drop(w);
+    std::fs::rename(&tmp, path).expect("Failed to write output");
+}
+
+const AMBIENT: f64 = 0.12;
+const MAX_DEPTH: i32 = 5;
+const SHADOW_EPS: f64 = 1e-4;
+
+// Direct lighting through one BSDF. Each light is estimated by sampling the
+// light and, with `bsdf_sampling`, also by sampling the BSDF; the estimates are
+// combined with the power heuristic. Delta lights only use light sampling.
+// Without `bsdf_sampling` the caller continues the path with a BSDF-sampled ray
+// instead, and the emission it finds is weighted by `mis_emission`.
+fn direct_light(
+    rec: &HitRecord,
+    wo: Vec3,
+    bsdf: &dyn Bxdf,
+    lights: &[Box<dyn Light>],
     world: &impl Hittable,
+    bsdf_sampling: bool,
+    sampler: &mut dyn Sampler,
 ) -> Color {
-    let ambient = 0.12;
+    let normal = rec.normal;
+    // Offset along the geometric normal: a perturbed shading normal can tilt
+    // below the surface and would start shadow rays inside the object.
+    let geo = rec.geometric_normal;
+    let shadow_origin = rec.p + geo * SHADOW_EPS;
+    let mut direct = Color::new(0.0, 0.0, 0.0);
+
+    for light in lights {
+        // Light sampling
+        let u = sampler.next_2d();
+        if let Some(ls) = light.sample_li(rec.p, u) {
+            let cos = Vec3::dot(normal, ls.wi);
+            if cos > 0.0 && Vec3::dot(geo, ls.wi) > 0.0 && ls.pdf > 0.0 {
+                let shadow_ray = Ray::new(shadow_origin, ls.wi);
+                stats::count_ray(RayKind::Shadow);
+                let in_shadow = stats::time_intersect(|| {
+                    world.hit(&shadow_ray, SHADOW_EPS, ls.dist - SHADOW_EPS)
+                })
+                .is_some();
+                if !in_shadow {
+                    let f = bsdf.eval(wo, ls.wi, normal);
+                    let weight = if light.is_delta() {
+                        1.0
+                    } else {
+                        power_heuristic(ls.pdf, bsdf.pdf(wo, ls.wi, normal))
+                    };
+                    direct += f * ls.li * (cos * weight / ls.pdf);
+                }
+            }
+        }
 
-    let to_light_vec = light.position - p;
-    let light_dist = to_light_vec.length();
-    let to_light_dir = to_light_vec / light_dist;
+        // BSDF sampling. The sample is drawn even when unused so every light
+        // consumes the same number of sampler dimensions.
+        let u = sampler.next_2d();
+        if light.is_delta() || !bsdf_sampling {
+            continue;
+        }
+        if let Some(bs) = bsdf
+            .sample(wo, normal, u)
+            .filter(|bs| Vec3::dot(geo, bs.wi) > 0.0)
+        {
+            let ray = Ray::new(shadow_origin, bs.wi);
+            stats::count_ray(RayKind::Shadow);
+            let t_hit = stats::time_intersect(|| world.hit(&ray, SHADOW_EPS, f64::INFINITY))
+                .map_or(f64::INFINITY, |h| h.t);
+            let le = light.le(&ray, t_hit);
+            if le.x > 0.0 || le.y > 0.0 || le.z > 0.0 {
+                let weight = power_heuristic(bs.pdf, light.pdf_li(&ray, t_hit));
+                let cos = Vec3::dot(normal, bs.wi);
+                direct += bs.f * le * (cos * weight / bs.pdf);
+            }
+        }
+    }
 
-    const SHADOW_EPS: f64 = 1e-4;
-    let shadow_origin = p + normal * SHADOW_EPS;
-    let shadow_ray = Ray::new(shadow_origin, to_light_dir);
-    let in_shadow = world
-        .hit(&shadow_ray, SHADOW_EPS, light_dist - SHADOW_EPS)
-        .is_some();
+    direct
+}
 
-    let diffuse = if in_shadow {
-        0.0
-    } else {
-        f64::max(0.0, Vec3::dot(normal, to_light_dir))
-    };
-    let lighting = ambient + diffuse;
-    (hit_color * lighting) * light.intensity
+// Emission `total` found by a BSDF-sampled ray that ended at `t_hit`. The part
+// coming from sampled lights was also estimated by light sampling, so it gets
+// its MIS weight; emission no light accounts for is kept in full.
+fn mis_emission(
+    total: Color,
+    r: &Ray,
+    t_hit: f64,
+    bsdf_pdf: f64,
+    lights: &[Box<dyn Light>],
+) -> Color {
+    let mut out = total;
+    for light in lights.iter().filter(|l| !l.is_delta()) {
+        let le = light.le(r, t_hit);
+        if le.x > 0.0 || le.y > 0.0 || le.z > 0.0 {
+            let weight = power_heuristic(bsdf_pdf, light.pdf_li(r, t_hit));
+            out = out - le * (1.0 - weight);
+        }
+    }
+    out
 }
 
-fn ray_color(r: &Ray, world: &impl Hittable, light: &PointLight, depth: i32) -> Color {
+// `bsdf_pdf` is the density of the BSDF sample that produced `r`, if any; it is
+// needed to MIS-weight the emission the ray finds.
+fn ray_color(
+    r: &Ray,
+    scene: &Scene,
+    depth: i32,
+    bsdf_pdf: Option<f64>,
+    sampler: &mut dyn Sampler,
+) -> Color {
     if depth <= 0 {
         return Color::new(0.0, 0.0, 0.0); // no contribution when we exceed bounce limit
     }
 
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
+    if let Some(mut rec) = stats::time_intersect(|| scene.world.hit(r, 0.001, f64::INFINITY)) {
+        rec.material.perturb_normal(&mut rec);
+
         // Local shading
-        let local = shade_lambert_with_shadow(rec.albedo, rec.normal, rec.p, light, world);
+        let wo = -r.direction.unit();
+        let mut emitted = rec.material.emitted(rec.front_face);
+        if let Some(pdf) = bsdf_pdf {
+            emitted = mis_emission(emitted, r, rec.t, pdf, &scene.lights);
+        }
+        let albedo = rec.material.albedo.value(rec.u, rec.v, rec.p);
+
+        if let Some(principled) = &rec.material.principled {
+            let (diffuse, glossy) = principled.lobes(albedo);
+            let local = emitted
+                + diffuse.albedo() * scene.ambient
+                + direct_light(
+                    &rec,
+                    wo,
+                    &diffuse,
+                    &scene.lights,
+                    &scene.world,
+                    true,
+                    sampler,
+                )
+                // At the last bounce there's no continuation to find the
+                // emission, so light sampling has to cover all of it
+                + direct_light(
+                    &rec,
+                    wo,
+                    &glossy,
+                    &scene.lights,
+                    &scene.world,
+                    depth <= 1,
+                    sampler,
+                );
+
+            // Glossy reflection: continue along a GGX-sampled direction
+            let u = sampler.next_2d();
+            let geo = rec.geometric_normal;
+            if let Some(bs) = glossy
+                .sample(wo, rec.normal, u)
+                .filter(|bs| Vec3::dot(geo, bs.wi) > 0.0)
+            {
+                let next = Ray::new(rec.p + geo * SHADOW_EPS, bs.wi);
+                // At the bounce limit the ray isn't traced
+                if depth > 1 {
+                    stats::count_ray(RayKind::Reflection);
+                }
+                let cos = Vec3::dot(rec.normal, bs.wi);
+                let li = ray_color(&next, scene, depth - 1, Some(bs.pdf), sampler);
+                return local + bs.f * li * (cos / bs.pdf);
+            }
+            return local;
+        }
+
+        let bsdf = Phong::new(albedo, rec.material.specular, rec.material.shininess);
+        let local = emitted
+            + albedo * scene.ambient
+            + direct_light(&rec, wo, &bsdf, &scene.lights, &scene.world, true, sampler);
 
         // Reflection
-        let refl = rec.reflectivity.clamp(0.0, 1.0);
+        let refl = rec.material.reflectivity.clamp(0.0, 1.0);
         if refl > 0.0 {
             const BIAS: f64 = 1e-4;
             let reflect_dir = reflect(r.direction.unit(), rec.normal).unit();
-            let reflect_ray = Ray::new(rec.p + rec.normal * BIAS, reflect_dir);
-            let reflected = ray_color(&reflect_ray, world, light, depth - 1);
+            let reflect_ray = Ray::new(rec.p + rec.geometric_normal * BIAS, reflect_dir);
+            if depth > 1 {
+                stats::count_ray(RayKind::Reflection);
+            }
+            let reflected = ray_color(&reflect_ray, scene, depth - 1, None, sampler);
             return local * (1.0 - refl) + reflected * refl;
         } else {
             return local;
@@ -89,26 +281,35 @@ fn ray_color(r: &Ray, world: &impl Hittable, light: &PointLight, depth: i32) ->
     } // <-- this closes the if-let block
 
     // Sky
-    let unit_dir = r.direction.unit();
-    let t = 0.5 * (unit_dir.y + 1.0);
-    (Color::new(1.0, 1.0, 1.0) * (1.0 - t)) + (Color::new(0.5, 0.7, 1.0) * t)
+    let sky = scene.background.radiance(r.direction);
+    match bsdf_pdf {
+        Some(pdf) => mis_emission(sky, r, f64::INFINITY, pdf, &scene.lights),
+        None => sky,
+    }
 }
 
-#[derive(Clone, Copy)]
+// Denoiser guides: albedo and shading normal where a camera ray first hits
+fn primary_features(r: &Ray, scene: &Scene) -> (Color, Vec3) {
+    stats::count_ray(RayKind::Guide);
+    match stats::time_intersect(|| scene.world.hit(r, 0.001, f64::INFINITY)) {
+        Some(mut rec) => {
+            rec.material.perturb_normal(&mut rec);
+            (rec.material.albedo.value(rec.u, rec.v, rec.p), rec.normal)
+        }
+        None => (Color::new(1.0, 1.0, 1.0), Vec3::new(0.0, 0.0, 0.0)),
+    }
+}
+
+#[derive(Clone, Copy, Default)]
 enum SceneKind {
     Sphere,
     CubePlaneDim,
+    #[default]
     All,
     AllAltCam,
     Custom,
 }
 
-impl Default for SceneKind {
-    fn default() -> Self {
-        SceneKind::All
-    }
-}
-
 #[derive(Default, Clone)]
 struct CamOverride {
     lookfrom: Option<Point3>,
@@ -117,6 +318,67 @@ struct CamOverride {
     fov: Option<f64>,
 }
 
+// --sky=physical settings
+#[derive(Clone)]
+struct SkyOpts {
+    physical: bool,
+    turbidity: f64,
+    sun_elevation: f64,           // degrees above the horizon
+    sun_azimuth: f64,             // degrees clockwise from north (-Z)
+    sun_date: Option<(u32, u32)>, // (month, day)
+    sun_time: Option<f64>,        // local solar time in hours
+    latitude: f64,
+    sun_intensity: f64,
+    sun_size: f64, // angular diameter in degrees
+}
+
+impl Default for SkyOpts {
+    fn default() -> Self {
+        Self {
+            physical: false,
+            turbidity: 3.0,
+            sun_elevation: 45.0,
+            sun_azimuth: 200.0,
+            sun_date: None,
+            sun_time: None,
+            latitude: 45.0,
+            sun_intensity: 1.0,
+            sun_size: 0.53,
+        }
+    }
+}
+
+// How often --progressive rewrites the output
+#[derive(Clone, Copy, Debug)]
+enum Snapshots {
+    Seconds(f64),
+    Passes(u32),
+}
+
+impl Default for Snapshots {
+    fn default() -> Self {
+        Snapshots::Seconds(10.0)
+    }
+}
+
+impl Snapshots {
+    // "30s" is seconds, a bare number counts passes
+    fn parse(s: &str) -> Option<Self> {
+        match s.strip_suffix('s') {
+            Some(secs) => secs
+                .parse::<f64>()
+                .ok()
+                .filter(|&v| v >= 0.0)
+                .map(Snapshots::Seconds),
+            None => s
+                .parse::<u32>()
+                .ok()
+                .filter(|&v| v > 0)
+                .map(Snapshots::Passes),
+        }
+    }
+}
+
 #[derive(Default)]
 struct Args {
     scene: SceneKind,
@@ -124,6 +386,27 @@ struct Args {
     height: i32,
     out: String,
     samples_per_pixel: i32,
+    sampler: SamplerKind,
+    seed: u64,
+    filter: Filter,
+    adaptive: Option<AdaptiveOpts>,
+    heatmap: Option<String>,
+    display: DisplayTransform,
+    denoise: Option<DenoiseOpts>,
+    noisy_out: Option<String>,
+    progressive: Option<Snapshots>,
+    checkpoint: Option<String>,
+    checkpoint_every: f64, // seconds
+    resume: bool,
+    time_limit: Option<f64>, // seconds
+    stats_json: Option<String>,
+    worker: Option<String>, // address to listen on
+    workers: Vec<String>,
+    tile_size: usize,
+    region: Option<Rect>, // film coordinates, rows from the bottom
+    full_frame: bool,
+    buffer_out: Option<String>,
+    fingerprint: u64, // hash of the flags that affect the rendered samples
 
     // NEW: camera override
     cam: CamOverride,
@@ -132,11 +415,23 @@ struct Args {
     light_pos: Option<Point3>,
     light_int: Option<Color>,
 
+    // extra lights (repeatable)
+    add_spot_lights: Vec<(Point3, Vec3, f64, f64, Color)>, // (pos, dir, inner, outer, intensity)
+    add_dir_lights: Vec<(Vec3, Color)>,                    // (direction, intensity)
+
+    // environment map (background + light)
+    envmap: Option<String>,
+    env_rotation: f64,
+    env_intensity: f64,
+    sky: SkyOpts,
+
     // NEW: custom objects (repeatable flags)
-    add_spheres: Vec<(Point3, f64, Color, f64)>, // (center, radius, albedo, refl)
-    add_planes: Vec<(Point3, Vec3, Color, f64)>, // (point, normal, albedo, refl)
-    add_cubes: Vec<(Point3, f64, Color, f64)>,   // (center, size, albedo, refl)
-    add_cylinders: Vec<(Point3, f64, f64, Color, f64)>, // (center, radius, half_h, albedo, refl)
+    add_spheres: Vec<(Point3, f64, Material)>, // (center, radius, material)
+    add_planes: Vec<(Point3, Vec3, Material, Option<Vec3>, f64)>, // (point, normal, material, tangent, uv_scale)
+    add_cubes: Vec<(Point3, f64, Material)>,                      // (center, size, material)
+    add_cylinders: Vec<(Point3, f64, f64, Material)>, // (center, radius, half_h, material)
+    add_triangles: Vec<([Point3; 3], Material)>,      // (vertices, material)
+    add_meshes: Vec<(String, Material)>,              // (OBJ path, material)
 }
 
 fn parse_resolution(s: &str) -> Option<(i32, i32)> {
@@ -160,7 +455,7 @@ fn parse_vec3(s: &str) -> Option<Vec3> {
 }
 
 fn clamp01(x: f64) -> f64 {
-    x.max(0.0).min(1.0)
+    x.clamp(0.0, 1.0)
 }
 
 fn parse_color_clamped01(s: &str) -> Option<Color> {
@@ -171,23 +466,124 @@ fn parse_color_nonneg(s: &str) -> Option<Color> {
     parse_vec3(s).map(|v| Color::new(v.x.max(0.0), v.y.max(0.0), v.z.max(0.0)))
 }
 
-fn split4(s: &str) -> Option<(&str, &str, &str, &str)> {
+// Positional fields, then trailing key=value options
+type Spec<'a> = (Vec<&'a str>, Vec<(&'a str, &'a str)>);
+
+// Splits "a; b; c; key=value; ..." into `n` positional fields followed by
+// optional key=value pairs. Returns None if fields are missing or malformed.
+fn split_spec(s: &str, n: usize) -> Option<Spec<'_>> {
     let s = dequote(s);
     let parts: Vec<&str> = s.split(';').map(|t| dequote(t.trim())).collect();
-    if parts.len() == 4 {
-        Some((parts[0], parts[1], parts[2], parts[3]))
-    } else {
-        None
+    if parts.len() < n {
+        return None;
     }
+    let mut opts = Vec::new();
+    for p in &parts[n..] {
+        let (k, v) = p.split_once('=')?;
+        opts.push((k.trim(), dequote(v.trim())));
+    }
+    Some((parts[..n].to_vec(), opts))
 }
-fn split5(s: &str) -> Option<(&str, &str, &str, &str, &str)> {
-    let s = dequote(s);
-    let parts: Vec<&str> = s.split(';').map(|t| dequote(t.trim())).collect();
-    if parts.len() == 5 {
-        Some((parts[0], parts[1], parts[2], parts[3], parts[4]))
-    } else {
-        None
+
+// "90", "90s", "5m" or "2h" -> seconds
+fn parse_duration(s: &str) -> Option<f64> {
+    let (num, scale) = match s.chars().last()? {
+        's' => (&s[..s.len() - 1], 1.0),
+        'm' => (&s[..s.len() - 1], 60.0),
+        'h' => (&s[..s.len() - 1], 3600.0),
+        _ => (s, 1.0),
+    };
+    num.parse::<f64>()
+        .ok()
+        .filter(|v| *v > 0.0)
+        .map(|v| v * scale)
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+// Base color + reflectivity, refined by the optional key=value material fields
+fn parse_material(color: &str, refl: &str, opts: &[(&str, &str)]) -> Option<Material> {
+    let base = parse_color_clamped01(color)?;
+    let mut m = Material::new(base, refl.parse::<f64>().ok()?.clamp(0.0, 1.0));
+
+    let mut checker: Option<Color> = None;
+    let mut checker_size = 0.5;
+    let mut image: Option<&str> = None;
+    let mut wrap = WrapMode::Repeat;
+    let mut noise: Option<Color> = None;
+    let mut noise_scale = 4.0;
+    let mut octaves = 1;
+    let mut normal_map: Option<&str> = None;
+    let mut bump_map: Option<&str> = None;
+    let mut bump_noise: Option<f64> = None;
+    for &(k, v) in opts {
+        match k {
+            "emit" => m.emission = parse_color_nonneg(v)?,
+            "two_sided" => m.two_sided = parse_bool(v)?,
+            "checker" => checker = Some(parse_color_clamped01(v)?),
+            "checker_size" => checker_size = v.parse::<f64>().ok().filter(|s| *s > 0.0)?,
+            "image" => image = Some(v),
+            "wrap" => wrap = WrapMode::parse(v)?,
+            "noise" => noise = Some(parse_color_clamped01(v)?),
+            "noise_scale" => noise_scale = v.parse::<f64>().ok()?,
+            "octaves" => octaves = v.parse::<u32>().ok()?.clamp(1, 16),
+            "normal_map" => normal_map = Some(v),
+            "bump_map" => bump_map = Some(v),
+            "bump_noise" => bump_noise = Some(v.parse::<f64>().ok()?),
+            "bump_scale" => m.bump_scale = v.parse::<f64>().ok()?,
+            "specular" => m.specular = parse_color_clamped01(v)?,
+            "metallic" | "roughness" | "specular_level" | "clearcoat" | "sheen" => {
+                // Any principled parameter opts the object into the GGX material
+                let x = v.parse::<f64>().ok().filter(|x| (0.0..=1.0).contains(x))?;
+                let p = m.principled.get_or_insert_with(Principled::default);
+                match k {
+                    "metallic" => p.metallic = x,
+                    "roughness" => p.roughness = x,
+                    "specular_level" => p.specular = x,
+                    "clearcoat" => p.clearcoat = x,
+                    _ => p.sheen = x,
+                }
+            }
+            "shininess" => m.shininess = v.parse::<f64>().ok().filter(|s| *s >= 0.0)?,
+            _ => return None,
+        }
     }
+
+    if let Some(path) = image {
+        match ImageTexture::load(path, wrap) {
+            Ok(tex) => m.albedo = Arc::new(tex),
+            Err(e) => eprintln!("warning: ignoring image texture: {e}"),
+        }
+    } else if let Some(other) = noise {
+        m.albedo = Arc::new(NoiseTexture::new(noise_scale, octaves, base, other));
+    }
+    if let Some(other) = checker {
+        let odd: Arc<dyn Texture> = Arc::new(SolidColor::new(other));
+        m.albedo = Arc::new(Checker::new(checker_size, m.albedo.clone(), odd));
+    }
+
+    if let Some(path) = normal_map {
+        match ImageTexture::load_data(path, wrap) {
+            Ok(tex) => m.normal_map = Some(Arc::new(tex)),
+            Err(e) => eprintln!("warning: ignoring normal map: {e}"),
+        }
+    }
+    if let Some(path) = bump_map {
+        match ImageTexture::load_data(path, wrap) {
+            Ok(tex) => m.bump_map = Some(Arc::new(tex)),
+            Err(e) => eprintln!("warning: ignoring bump map: {e}"),
+        }
+    } else if let Some(scale) = bump_noise {
+        let (black, white) = (Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        m.bump_map = Some(Arc::new(NoiseTexture::new(scale, octaves, black, white)));
+    }
+    Some(m)
 }
 
 fn dequote(s: &str) -> &str {
@@ -201,23 +597,125 @@ fn dequote(s: &str) -> &str {
     }
 }
 
+// Replaces each --scene-file=<path> with the flags listed in that file: one
+// flag per line, blank lines and lines starting with '#' ignored.
+fn expand_scene_files(args: Vec<String>, depth: u32) -> Vec<String> {
+    let mut out = Vec::new();
+    for a in args {
+        let Some(path) = a.strip_prefix("--scene-file=").map(dequote) else {
+            out.push(a);
+            continue;
+        };
+        if depth >= 8 {
+            eprintln!("warning: ignoring {path}: scene files nested too deeply");
+            continue;
+        }
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let lines = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(String::from)
+                    .collect();
+                out.extend(expand_scene_files(lines, depth + 1));
+            }
+            Err(e) => eprintln!("warning: ignoring scene file {path}: {e}"),
+        }
+    }
+    out
+}
+
+// Flags that only affect how, when or where the samples are computed and how
+// results are written. A checkpoint can be resumed, and a worker can serve a
+// coordinator, with different values for these.
+const NON_RENDER_FLAGS: [&str; 21] = [
+    "--out=",
+    "--noisy-out=",
+    "--heatmap=",
+    "--denoise",
+    "--exposure=",
+    "--tonemap=",
+    "--transfer=",
+    "--progressive",
+    "--snapshot-every=",
+    "--checkpoint=",
+    "--checkpoint-every=",
+    "--resume",
+    "--time-limit=",
+    "--stats-json=",
+    "--worker=",
+    "--workers=",
+    "--tile-size=",
+    "--region=",
+    "--full-frame",
+    "--buffer-out=",
+    "--help",
+];
+
+fn render_fingerprint(args: &[String]) -> u64 {
+    let mut bytes = Vec::new();
+    for a in args {
+        if !NON_RENDER_FLAGS.iter().any(|f| a.starts_with(f)) {
+            bytes.extend_from_slice(a.as_bytes());
+            bytes.push(0);
+        }
+    }
+    checkpoint::fnv1a(&bytes)
+}
+
 fn parse_args() -> Args {
     let mut scene = SceneKind::All;
     let mut width = 400;
     let mut height = 300;
     let mut out: Option<String> = None;
     let mut spp = 16;
+    let mut sampler = SamplerKind::default();
+    let mut seed = 0;
+    let mut filter_kind = FilterKind::Box;
+    let mut filter_radius: Option<f64> = None;
+    let mut noise_threshold: Option<f64> = None;
+    let mut min_spp: Option<i32> = None;
+    let mut max_spp: Option<i32> = None;
+    let mut heatmap: Option<String> = None;
+    let mut display = DisplayTransform::default();
+    let mut denoise: Option<DenoiseOpts> = None;
+    let mut noisy_out: Option<String> = None;
+    let mut progressive: Option<Snapshots> = None;
+    let mut checkpoint: Option<String> = None;
+    let mut checkpoint_every = 60.0;
+    let mut resume = false;
+    let mut time_limit: Option<f64> = None;
+    let mut stats_json: Option<String> = None;
+    let mut worker: Option<String> = None;
+    let mut workers: Vec<String> = Vec::new();
+    let mut tile_size = 32;
+    let mut region: Option<[usize; 4]> = None;
+    let mut full_frame = false;
+    let mut buffer_out: Option<String> = None;
 
     let mut cam = CamOverride::default();
     let mut light_pos: Option<Point3> = None;
     let mut light_int: Option<Color> = None;
 
-    let mut add_spheres: Vec<(Point3, f64, Color, f64)> = Vec::new();
-    let mut add_planes: Vec<(Point3, Vec3, Color, f64)> = Vec::new();
-    let mut add_cubes: Vec<(Point3, f64, Color, f64)> = Vec::new();
-    let mut add_cyls: Vec<(Point3, f64, f64, Color, f64)> = Vec::new();
+    let mut add_spot_lights: Vec<(Point3, Vec3, f64, f64, Color)> = Vec::new();
+    let mut add_dir_lights: Vec<(Vec3, Color)> = Vec::new();
+
+    let mut envmap: Option<String> = None;
+    let mut env_rotation = 0.0;
+    let mut env_intensity = 1.0;
+    let mut sky = SkyOpts::default();
+
+    let mut add_spheres: Vec<(Point3, f64, Material)> = Vec::new();
+    let mut add_planes: Vec<(Point3, Vec3, Material, Option<Vec3>, f64)> = Vec::new();
+    let mut add_cubes: Vec<(Point3, f64, Material)> = Vec::new();
+    let mut add_cyls: Vec<(Point3, f64, f64, Material)> = Vec::new();
+    let mut add_triangles: Vec<([Point3; 3], Material)> = Vec::new();
+    let mut add_meshes: Vec<(String, Material)> = Vec::new();
 
-    for a in std::env::args().skip(1) {
+    let argv = expand_scene_files(std::env::args().skip(1).collect(), 0);
+    let fingerprint = render_fingerprint(&argv);
+    for a in argv {
         if let Some(val0) = a.strip_prefix("--scene=") {
             let val = dequote(val0);
             scene = match val {
@@ -242,6 +740,110 @@ fn parse_args() -> Args {
             if let Ok(v) = val.parse::<i32>() {
                 spp = v.max(1);
             }
+        } else if let Some(val0) = a.strip_prefix("--sampler=") {
+            if let Some(k) = SamplerKind::parse(dequote(val0)) {
+                sampler = k;
+            }
+        } else if let Some(val0) = a.strip_prefix("--filter=") {
+            if let Some(k) = FilterKind::parse(dequote(val0)) {
+                filter_kind = k;
+            }
+        } else if let Some(val0) = a.strip_prefix("--filter-radius=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                if v > 0.0 {
+                    filter_radius = Some(v);
+                }
+            }
+        } else if let Some(val0) = a.strip_prefix("--noise-threshold=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                if v > 0.0 {
+                    noise_threshold = Some(v);
+                }
+            }
+        } else if let Some(val0) = a.strip_prefix("--min-spp=") {
+            if let Ok(v) = dequote(val0).parse::<i32>() {
+                min_spp = Some(v.max(1));
+            }
+        } else if let Some(val0) = a.strip_prefix("--max-spp=") {
+            if let Ok(v) = dequote(val0).parse::<i32>() {
+                max_spp = Some(v.max(1));
+            }
+        } else if let Some(val0) = a.strip_prefix("--heatmap=") {
+            heatmap = Some(dequote(val0).to_string());
+        } else if let Some(val0) = a.strip_prefix("--exposure=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                display.exposure = v;
+            }
+        } else if let Some(val0) = a.strip_prefix("--tonemap=") {
+            if let Some(t) = ToneMap::parse(dequote(val0)) {
+                display.tonemap = t;
+            }
+        } else if let Some(val0) = a.strip_prefix("--transfer=") {
+            if let Some(t) = Transfer::parse(dequote(val0)) {
+                display.transfer = t;
+            }
+        } else if a == "--denoise" {
+            denoise.get_or_insert_with(DenoiseOpts::default);
+        } else if let Some(val0) = a.strip_prefix("--denoise-radius=") {
+            if let Ok(v) = dequote(val0).parse::<usize>() {
+                denoise.get_or_insert_with(DenoiseOpts::default).radius = v.max(1);
+            }
+        } else if let Some(val0) = a.strip_prefix("--noisy-out=") {
+            noisy_out = Some(dequote(val0).to_string());
+        } else if a == "--progressive" {
+            progressive.get_or_insert_with(Snapshots::default);
+        } else if let Some(val0) = a.strip_prefix("--snapshot-every=") {
+            if let Some(every) = Snapshots::parse(dequote(val0)) {
+                progressive = Some(every);
+            }
+        } else if let Some(val0) = a.strip_prefix("--checkpoint=") {
+            checkpoint = Some(dequote(val0).to_string());
+        } else if let Some(val0) = a.strip_prefix("--checkpoint-every=") {
+            if let Ok(v) = dequote(val0).trim_end_matches('s').parse::<f64>() {
+                checkpoint_every = v.max(0.0);
+            }
+        } else if a == "--resume" {
+            resume = true;
+        } else if let Some(val0) = a.strip_prefix("--worker=") {
+            worker = Some(dequote(val0).to_string());
+        } else if let Some(val0) = a.strip_prefix("--workers=") {
+            workers = dequote(val0)
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect();
+        } else if let Some(val0) = a.strip_prefix("--tile-size=") {
+            if let Ok(v) = dequote(val0).parse::<usize>() {
+                tile_size = v.max(1);
+            }
+        } else if let Some(val0) = a.strip_prefix("--region=") {
+            // Every field has to parse; a bad one doesn't just disappear
+            let v: Vec<usize> = dequote(val0)
+                .split(',')
+                .map(|s| s.trim().parse())
+                .collect::<Result<_, _>>()
+                .unwrap_or_default();
+            match v[..] {
+                [x0, y0, x1, y1] => region = Some([x0, y0, x1, y1]),
+                _ => eprintln!("warning: --region wants x0,y0,x1,y1; rendering everything"),
+            }
+        } else if a == "--full-frame" {
+            full_frame = true;
+        } else if let Some(val0) = a.strip_prefix("--buffer-out=") {
+            buffer_out = Some(dequote(val0).to_string());
+        } else if let Some(val0) = a.strip_prefix("--stats-json=") {
+            stats_json = Some(dequote(val0).to_string());
+        } else if let Some(val0) = a.strip_prefix("--time-limit=") {
+            if let Some(secs) = parse_duration(dequote(val0)) {
+                time_limit = Some(secs);
+                // Stopping early needs an image that is complete at every point
+                progressive.get_or_insert_with(Snapshots::default);
+            }
+        } else if let Some(val0) = a.strip_prefix("--seed=") {
+            if let Ok(v) = dequote(val0).parse::<u64>() {
+                seed = v;
+            }
 
         // --- camera ---
         } else if let Some(val0) = a.strip_prefix("--lookfrom=") {
@@ -276,62 +878,167 @@ fn parse_args() -> Args {
             if let Some(c) = parse_color_nonneg(val) {
                 light_int = Some(c);
             }
+        } else if let Some(val0) = a.strip_prefix("--add-spot-light=") {
+            let val = dequote(val0);
+            if let Some(l) = split_spec(val, 5).and_then(|(f, _)| {
+                Some((
+                    parse_vec3(f[0])?,
+                    parse_vec3(f[1])?,
+                    f[2].parse::<f64>().ok()?,
+                    f[3].parse::<f64>().ok()?,
+                    parse_color_nonneg(f[4])?,
+                ))
+            }) {
+                add_spot_lights.push(l);
+            }
+        } else if let Some(val0) = a.strip_prefix("--add-dir-light=") {
+            let val = dequote(val0);
+            if let Some(l) = split_spec(val, 2)
+                .and_then(|(f, _)| Some((parse_vec3(f[0])?, parse_color_nonneg(f[1])?)))
+            {
+                add_dir_lights.push(l);
+            }
+
+        // --- environment ---
+        } else if let Some(val0) = a.strip_prefix("--envmap=") {
+            envmap = Some(dequote(val0).to_string());
+        } else if let Some(val0) = a.strip_prefix("--env-rotation=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                env_rotation = v;
+            }
+        } else if let Some(val0) = a.strip_prefix("--env-intensity=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                env_intensity = v.max(0.0);
+            }
+        } else if let Some(val0) = a.strip_prefix("--sky=") {
+            match dequote(val0) {
+                "physical" => sky.physical = true,
+                "gradient" => sky.physical = false,
+                _ => {}
+            }
+        } else if let Some(val0) = a.strip_prefix("--turbidity=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                sky.turbidity = v;
+            }
+        } else if let Some(val0) = a.strip_prefix("--sun-elevation=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                sky.sun_elevation = v;
+            }
+        } else if let Some(val0) = a.strip_prefix("--sun-azimuth=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                sky.sun_azimuth = v;
+            }
+        } else if let Some(val0) = a.strip_prefix("--sun-date=") {
+            // YYYY-MM-DD (the year is ignored) or MM-DD
+            let parts: Vec<&str> = dequote(val0).split('-').collect();
+            let md = &parts[parts.len().saturating_sub(2)..];
+            if let [m, d] = md {
+                if let (Ok(m), Ok(d)) = (m.parse::<u32>(), d.parse::<u32>()) {
+                    sky.sun_date = Some((m, d));
+                }
+            }
+        } else if let Some(val0) = a.strip_prefix("--sun-time=") {
+            if let Some((h, m)) = dequote(val0).split_once(':') {
+                if let (Ok(h), Ok(m)) = (h.parse::<f64>(), m.parse::<f64>()) {
+                    sky.sun_time = Some(h + m / 60.0);
+                }
+            }
+        } else if let Some(val0) = a.strip_prefix("--latitude=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                sky.latitude = v.clamp(-90.0, 90.0);
+            }
+        } else if let Some(val0) = a.strip_prefix("--sun-intensity=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                sky.sun_intensity = v.max(0.0);
+            }
+        } else if let Some(val0) = a.strip_prefix("--sun-size=") {
+            if let Ok(v) = dequote(val0).parse::<f64>() {
+                sky.sun_size = v.max(0.0);
+            }
 
         // --- objects (repeatable) ---
         } else if let Some(val0) = a.strip_prefix("--add-sphere=") {
             let val = dequote(val0);
-            if let Some((p, rad, col, refl)) = split4(val).and_then(|(p, r, c, f)| {
+            if let Some((p, rad, mat)) = split_spec(val, 4).and_then(|(f, opts)| {
                 Some((
-                    parse_vec3(dequote(p))?,
-                    r.parse::<f64>().ok()?,
-                    parse_color_clamped01(dequote(c))?,
-                    f.parse::<f64>().ok()?,
+                    parse_vec3(f[0])?,
+                    f[1].parse::<f64>().ok()?,
+                    parse_material(f[2], f[3], &opts)?,
                 ))
             }) {
-                add_spheres.push((Point3::new(p.x, p.y, p.z), rad, col, refl.clamp(0.0, 1.0)));
+                add_spheres.push((p, rad, mat));
             }
         } else if let Some(val0) = a.strip_prefix("--add-plane=") {
             let val = dequote(val0);
-            if let Some((p, n, col, refl)) = split4(val).and_then(|(p, n, c, f)| {
+            // An infinite plane has no area to sample, so it can't be a light
+            if split_spec(val, 4).is_some_and(|(_, opts)| opts.iter().any(|(k, _)| *k == "emit")) {
+                eprintln!(
+                    "error: --add-plane doesn't take emit=; use a cube or triangles as a light"
+                );
+                std::process::exit(1);
+            }
+            if let Some(plane) = split_spec(val, 4).and_then(|(f, opts)| {
+                // Texture frame options belong to the plane, the rest to its material
+                let (frame, opts): (Vec<_>, Vec<_>) = opts
+                    .into_iter()
+                    .partition(|(k, _)| *k == "tangent" || *k == "uv_scale");
+                let mut tangent = None;
+                let mut uv_scale = 1.0;
+                for (k, v) in frame {
+                    match k {
+                        "tangent" => tangent = Some(parse_vec3(v)?),
+                        _ => uv_scale = v.parse::<f64>().ok().filter(|s| *s > 0.0)?,
+                    }
+                }
                 Some((
-                    parse_vec3(dequote(p))?,
-                    parse_vec3(dequote(n))?,
-                    parse_color_clamped01(dequote(c))?,
-                    f.parse::<f64>().ok()?,
+                    parse_vec3(f[0])?,
+                    parse_vec3(f[1])?,
+                    parse_material(f[2], f[3], &opts)?,
+                    tangent,
+                    uv_scale,
                 ))
             }) {
-                add_planes.push((Point3::new(p.x, p.y, p.z), n, col, refl.clamp(0.0, 1.0)));
+                add_planes.push(plane);
             }
         } else if let Some(val0) = a.strip_prefix("--add-cube=") {
             let val = dequote(val0);
-            if let Some((p, size, col, refl)) = split4(val).and_then(|(p, s, c, f)| {
+            if let Some((p, size, mat)) = split_spec(val, 4).and_then(|(f, opts)| {
                 Some((
-                    parse_vec3(dequote(p))?,
-                    s.parse::<f64>().ok()?,
-                    parse_color_clamped01(dequote(c))?,
-                    f.parse::<f64>().ok()?,
+                    parse_vec3(f[0])?,
+                    f[1].parse::<f64>().ok()?,
+                    parse_material(f[2], f[3], &opts)?,
                 ))
             }) {
-                add_cubes.push((Point3::new(p.x, p.y, p.z), size, col, refl.clamp(0.0, 1.0)));
+                add_cubes.push((p, size, mat));
             }
         } else if let Some(val0) = a.strip_prefix("--add-cylinder=") {
             let val = dequote(val0);
-            if let Some((p, rad, hh, col, refl)) = split5(val).and_then(|(p, r, hh, c, f)| {
+            if let Some((p, rad, hh, mat)) = split_spec(val, 5).and_then(|(f, opts)| {
                 Some((
-                    parse_vec3(dequote(p))?,
-                    r.parse::<f64>().ok()?,
-                    hh.parse::<f64>().ok()?,
-                    parse_color_clamped01(dequote(c))?,
-                    f.parse::<f64>().ok()?,
+                    parse_vec3(f[0])?,
+                    f[1].parse::<f64>().ok()?,
+                    f[2].parse::<f64>().ok()?,
+                    parse_material(f[3], f[4], &opts)?,
                 ))
             }) {
-                add_cyls.push((
-                    Point3::new(p.x, p.y, p.z),
-                    rad,
-                    hh,
-                    col,
-                    refl.clamp(0.0, 1.0),
-                ));
+                add_cyls.push((p, rad, hh, mat));
+            }
+        } else if let Some(val0) = a.strip_prefix("--add-triangle=") {
+            let val = dequote(val0);
+            if let Some((v, mat)) = split_spec(val, 5).and_then(|(f, opts)| {
+                Some((
+                    [parse_vec3(f[0])?, parse_vec3(f[1])?, parse_vec3(f[2])?],
+                    parse_material(f[3], f[4], &opts)?,
+                ))
+            }) {
+                add_triangles.push((v, mat));
+            }
+        } else if let Some(val0) = a.strip_prefix("--add-mesh=") {
+            let val = dequote(val0);
+            if let Some((path, mat)) = split_spec(val, 3)
+                .and_then(|(f, opts)| Some((f[0].to_string(), parse_material(f[1], f[2], &opts)?)))
+            {
+                add_meshes.push((path, mat));
             }
         } else if a == "--help" {
             eprintln!(
@@ -342,6 +1049,56 @@ Required:
   --res=<WxH>                 Image resolution (e.g. 800x600)
   --out=<file.ppm>            Output file
   --spp=<N>                   Samples per pixel (antialiasing)
+  --scene-file=<path>         Read more flags from a file, one per line ('#' comments)
+
+Sampling:
+  --sampler=<random|stratified|halton|sobol>  Sample pattern (default random)
+  --seed=<n>                  Seed for all sampling decisions (default 0); same seed = same image
+  --filter=<box|tent|gaussian|mitchell|lanczos>  Pixel reconstruction filter (default box)
+  --filter-radius=<px>        Filter radius in pixels (default: box 0.5, tent 1, gaussian 1.5,
+                              mitchell 2, lanczos 2)
+
+Output:
+  --exposure=<stops>          Scale radiance by 2^stops before tone mapping (default 0)
+  --tonemap=<none|reinhard|aces|agx>  Highlight roll-off; none clips at 1 (default none)
+  --transfer=<srgb|gamma2>    Output encoding: exact sRGB curve, or the old sqrt gamma (default srgb)
+  --denoise                   Filter the result guided by first-hit albedo and normals
+  --denoise-radius=<px>       Denoiser window half-width (default 5; implies --denoise)
+  --noisy-out=<file.ppm>      With --denoise, also write the image before denoising
+
+Progressive:
+  --progressive               Render in passes of 1 sample per pixel, rewriting --out as it goes
+  --snapshot-every=<Ns|N>     Rewrite every N seconds (\"30s\") or N passes (\"8\"); default 10s.
+                              Implies --progressive
+  --checkpoint=<path>         Save the render state there periodically and when done
+  --checkpoint-every=<secs>   Checkpoint interval (default 60)
+  --resume                    Continue from --checkpoint instead of starting over
+  --time-limit=<90s|5m|2h>    Stop after this much wall-clock time and write what is done so far.
+                              Implies --progressive
+  --stats-json=<path>         Also write the end-of-render statistics as JSON
+
+Distributed:
+  --worker=<host:port>        Listen there and render tiles for a coordinator, until killed
+  --workers=<host:port,...>   Render on these workers, started with the same scene and settings
+  --tile-size=<px>            Tile edge length for --workers (default 32)
+
+Region:
+  --region=<x0,y0,x1,y1>      Only render pixels x0..x1, y0..y1 (exclusive; y down from the top)
+                              and write just that part of the image
+  --full-frame                With --region, write the full-size image, black outside the region
+
+Merging (rt merge --help for details):
+  --buffer-out=<path>         Also write the region's float accumulation buffer and sample counts
+  merge [FLAGS] <input>...    Subcommand: combine float buffers or images into one image
+
+Comparing (rt diff --help for details):
+  diff [FLAGS] <ref> <test>   Subcommand: print RMSE, PSNR, SSIM and FLIP, write an error map
+
+Adaptive sampling:
+  --noise-threshold=<x>       Sample each pixel until its relative error is below x (e.g. 0.01)
+  --min-spp=<N>               Samples every pixel takes first (default min(16, max-spp))
+  --max-spp=<N>               Upper bound per pixel (default --spp)
+  --heatmap=<file.ppm>        Also write samples taken per pixel (black = min, white = max)
 
 Camera:
   --lookfrom=x,y,z            Camera position
@@ -352,16 +1109,66 @@ Camera:
 Light:
   --light-pos=x,y,z           Point light position
   --light-int=r,g,b           Light intensity (color multiplier)
+  --add-spot-light=\"px,py,pz; dx,dy,dz; inner_deg; outer_deg; r,g,b\"
+                              Spot light aimed along d (repeatable)
+  --add-dir-light=\"dx,dy,dz; r,g,b\"
+                              Directional light travelling along d (repeatable)
+
+Environment:
+  --envmap=<file.hdr|file.pfm> Equirectangular HDR image used as sky and light
+  --env-rotation=<deg>        Rotate the environment around +Y
+  --env-intensity=<x>         Environment radiance multiplier (default 1)
+  --sky=<gradient|physical>   Procedural gradient (default) or Preetham daylight sky
+  --turbidity=<t>             Atmospheric haze for the physical sky, 1.7-10 (default 3)
+  --sun-elevation=<deg>       Sun height above the horizon (default 45)
+  --sun-azimuth=<deg>         Sun compass direction, north = -Z, east = +X (default 200)
+  --sun-date=YYYY-MM-DD       With --sun-time, place the sun from the calendar
+  --sun-time=HH:MM            Local solar time (12:00 = solar noon)
+  --latitude=<deg>            Observer latitude for --sun-time (default 45)
+  --sun-intensity=<x>         Sun brightness (default 1)
+  --sun-size=<deg>            Sun angular diameter; larger = softer shadows (default 0.53)
 
 Objects (repeatable):
   --add-sphere=\"cx,cy,cz; radius; r,g,b; reflect\"
   --add-plane=\"px,py,pz; nx,ny,nz; r,g,b; reflect\"
   --add-cube=\"cx,cy,cz; size; r,g,b; reflect\"
   --add-cylinder=\"cx,cy,cz; radius; half_h; r,g,b; reflect\"
+  --add-triangle=\"x,y,z; x,y,z; x,y,z; r,g,b; reflect\"
+  --add-mesh=\"file.obj; r,g,b; reflect\"
+
+Material options (append to any object as extra fields):
+  emit=r,g,b                  Emitted radiance; emissive objects act as lights (not planes)
+  two_sided=0|1               Emit from both faces (default 0)
+  checker=r,g,b               3D checkerboard of the base color and this color
+  checker_size=<s>            Checker cell size in world units (default 0.5)
+  image=<file.png|file.ppm>   Image texture (bilinear) replacing the base color
+  wrap=repeat|clamp|mirror    Image wrap mode (default repeat)
+  noise=r,g,b                 Perlin noise between the base color and this color
+  noise_scale=<f>             Noise frequency (default 4)
+  octaves=<n>                 Noise octaves; >1 gives fBm (default 1)
+  normal_map=<file>           Tangent-space normal map (uses wrap=)
+  bump_map=<file>             Height map; brighter = raised (uses wrap=)
+  bump_noise=<f>              Procedural Perlin height map of this frequency (uses octaves=)
+  bump_scale=<s>              Bump height in world units (default 0.02)
+  specular=r,g,b              Blinn-Phong highlight color (default 0,0,0 = none)
+  shininess=<n>               Highlight exponent; higher = smaller, sharper (default 32)
+
+Principled (GGX) material options, each in [0,1]; any of them switches the object
+to the physically based material (reflect, specular and shininess are then ignored):
+  metallic=<x>                0 = dielectric, 1 = metal tinted by the base color (default 0)
+  roughness=<x>               Microfacet roughness (default 0.5)
+  specular_level=<x>          Dielectric reflectance; 0.5 = 4% head-on (default 0.5)
+  clearcoat=<x>               Glossy clear lacquer layer on top (default 0)
+  sheen=<x>                   Soft grazing-angle sheen for cloth (default 0)
+
+Plane options:
+  tangent=x,y,z               Texture u direction, projected onto the plane (default +X)
+  uv_scale=<s>                World units per texture tile (default 1)
 
 Notes:
   - If any --add-* is provided, scene switches to custom automatically.
   - Reflectivity is 0.0 (matte) to 1.0 (mirror).
+  - Triangles face the side their vertices wind counter-clockwise around.
 "
             );
             std::process::exit(0);
@@ -375,6 +1182,8 @@ Notes:
         || !add_planes.is_empty()
         || !add_cubes.is_empty()
         || !add_cyls.is_empty()
+        || !add_triangles.is_empty()
+        || !add_meshes.is_empty()
     {
         scene = SceneKind::Custom;
     }
@@ -388,6 +1197,20 @@ Notes:
     }
     .to_string();
 
+    // --region counts rows from the top like the output image; the film counts
+    // them from the bottom
+    let (w, h) = (width as usize, height as usize);
+    let region = region.and_then(|[x0, y0, x1, y1]| {
+        let (x1, y1) = (x1.min(w), y1.min(h));
+        if x0 >= x1 || y0 >= y1 {
+            eprintln!(
+                "warning: --region is empty or outside the {w}x{h} frame; rendering everything"
+            );
+            return None;
+        }
+        Some(Rect::new(x0, h - y1, x1, h - y0))
+    });
+
     // Return the parsed args
     Args {
         scene,
@@ -395,22 +1218,75 @@ Notes:
         height,
         out: out.unwrap_or(default_out),
         samples_per_pixel: spp,
+        sampler,
+        seed,
+        filter: Filter::new(
+            filter_kind,
+            filter_radius.unwrap_or(filter_kind.default_radius()),
+        ),
+        adaptive: noise_threshold.map(|threshold| {
+            let max_spp = max_spp.unwrap_or(spp) as u32;
+            AdaptiveOpts {
+                threshold,
+                min_spp: min_spp.map_or(16.min(max_spp), |m| (m as u32).min(max_spp)),
+                max_spp,
+            }
+        }),
+        heatmap,
+        display,
+        denoise,
+        noisy_out,
+        progressive,
+        checkpoint,
+        checkpoint_every,
+        resume,
+        time_limit,
+        stats_json,
+        worker,
+        workers,
+        tile_size,
+        region,
+        full_frame,
+        buffer_out,
+        fingerprint,
         cam,
         light_pos,
         light_int,
+        add_spot_lights,
+        add_dir_lights,
+        envmap,
+        env_rotation,
+        env_intensity,
+        sky,
         add_spheres,
         add_planes,
         add_cubes,
         add_cylinders: add_cyls,
+        add_triangles,
+        add_meshes,
     }
 }
 
 struct Scene {
     world: HittableList,
-    light: PointLight,
+    lights: Vec<Box<dyn Light>>,
+    ambient: Color,
+    background: Background,
     cam: Camera,
 }
 
+impl Scene {
+    fn new(world: HittableList, light: PointLight, cam: Camera) -> Self {
+        Self {
+            world,
+            ambient: light.intensity * AMBIENT,
+            lights: vec![Box::new(light)],
+            background: Background::Gradient,
+            cam,
+        }
+    }
+}
+
 fn build_scene(args: &Args) -> Scene {
     let aspect_ratio = args.width as f64 / args.height as f64;
 
@@ -430,7 +1306,7 @@ fn build_scene(args: &Args) -> Scene {
         Camera::new(lf, la, vup, fov, aspect_ratio)
     };
 
-    match args.scene {
+    let mut scene = match args.scene {
         SceneKind::Sphere => {
             let mut world = HittableList::new();
             world.add(Box::new(Plane::new(
@@ -452,7 +1328,7 @@ fn build_scene(args: &Args) -> Scene {
                 90.0,
             );
 
-            Scene { world, light, cam }
+            Scene::new(world, light, cam)
         }
 
         // 2) Flat plane + cube with lower brightness than sphere image
@@ -479,7 +1355,7 @@ fn build_scene(args: &Args) -> Scene {
                 90.0,
             );
 
-            Scene { world, light, cam }
+            Scene::new(world, light, cam)
         }
 
         // 3) All objects
@@ -519,7 +1395,7 @@ fn build_scene(args: &Args) -> Scene {
                 90.0,
             );
 
-            Scene { world, light, cam }
+            Scene::new(world, light, cam)
         }
 
         // 4) All objects, different camera (alternate perspective)
@@ -568,22 +1444,53 @@ fn build_scene(args: &Args) -> Scene {
                 75.0,
             );
 
-            Scene { world, light, cam }
+            Scene::new(world, light, cam)
         }
         SceneKind::Custom => {
             let mut world = HittableList::new();
+            let mut emitters: Vec<Arc<dyn Shape>> = Vec::new();
+            let mut add_shape = |world: &mut HittableList, shape: Arc<dyn Shape>| {
+                if shape.material().is_emissive() {
+                    emitters.push(shape.clone());
+                }
+                world.add(Box::new(shape));
+            };
 
-            for (p, n, col, refl) in &args.add_planes {
-                world.add(Box::new(Plane::new(*p, (*n).unit(), *col, *refl)));
+            for (p, n, mat, tangent, uv_scale) in &args.add_planes {
+                let plane = Plane::with_material(*p, (*n).unit(), mat.clone());
+                let tangent = tangent.unwrap_or(plane.tangent);
+                let plane = plane.with_uv_frame(tangent, *uv_scale);
+                world.add(Box::new(plane));
+            }
+            for (c, r, mat) in &args.add_spheres {
+                add_shape(
+                    &mut world,
+                    Arc::new(Sphere::with_material(*c, *r, mat.clone())),
+                );
             }
-            for (c, r, col, refl) in &args.add_spheres {
-                world.add(Box::new(Sphere::new(*c, *r, *col, *refl)));
+            for (c, size, mat) in &args.add_cubes {
+                add_shape(
+                    &mut world,
+                    Arc::new(Cube::with_material(*c, *size, mat.clone())),
+                );
             }
-            for (c, size, col, refl) in &args.add_cubes {
-                world.add(Box::new(Cube::from_center_size(*c, *size, *col, *refl)));
+            for (c, rad, hh, mat) in &args.add_cylinders {
+                add_shape(
+                    &mut world,
+                    Arc::new(Cylinder::with_material(*c, *rad, *hh, mat.clone())),
+                );
             }
-            for (c, rad, hh, col, refl) in &args.add_cylinders {
-                world.add(Box::new(Cylinder::new(*c, *rad, *hh, *col, *refl)));
+            for (v, mat) in &args.add_triangles {
+                add_shape(
+                    &mut world,
+                    Arc::new(Triangle::new(v[0], v[1], v[2], mat.clone())),
+                );
+            }
+            for (path, mat) in &args.add_meshes {
+                match Mesh::load_obj(path, mat.clone()) {
+                    Ok(mesh) => add_shape(&mut world, Arc::new(mesh)),
+                    Err(e) => eprintln!("warning: skipping mesh: {e}"),
+                }
             }
 
             // sensible defaults if user didn't add any plane/light/cam
@@ -603,13 +1510,477 @@ fn build_scene(args: &Args) -> Scene {
                 75.0,
             );
 
-            Scene { world, light, cam }
+            let mut scene = Scene::new(world, light, cam);
+            for shape in emitters {
+                scene.lights.push(Box::new(AreaLight::new(shape)));
+            }
+            scene
+        }
+    };
+
+    for &(pos, dir, inner, outer, int) in &args.add_spot_lights {
+        scene
+            .lights
+            .push(Box::new(SpotLight::new(pos, dir, inner, outer, int)));
+    }
+    for &(dir, int) in &args.add_dir_lights {
+        scene.lights.push(Box::new(DirectionalLight::new(dir, int)));
+    }
+
+    if let Some(path) = &args.envmap {
+        match EnvMap::load(path, args.env_rotation, args.env_intensity) {
+            Ok(env) => {
+                let env = Arc::new(env);
+                scene.lights.push(Box::new(env.clone()));
+                scene.background = Background::Env(env);
+            }
+            Err(e) => eprintln!("warning: ignoring environment map: {e}"),
+        }
+    } else if args.sky.physical {
+        let o = &args.sky;
+        let (elevation, azimuth) = match o.sun_time {
+            Some(hours) => {
+                let (month, day) = o.sun_date.unwrap_or((6, 21));
+                sky::solar_position(month, day, hours, o.latitude)
+            }
+            None => (o.sun_elevation, o.sun_azimuth),
+        };
+        let sun_dir = sky::sun_direction(elevation, azimuth);
+        let preetham = PreethamSky::new(sun_dir, o.turbidity);
+        // No direct sun once it has set; the sky alone still lights the scene
+        let sun_int = if elevation > 0.0 {
+            preetham.sun_transmittance() * o.sun_intensity
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+        let sun = Arc::new(SunLight::new(sun_dir, sun_int, o.sun_size));
+        let baked = Arc::new(preetham.to_envmap(512, 256, args.env_intensity));
+        // The sky replaces the default point light (Scene::new puts it first)
+        // and the ambient term derived from it
+        if args.light_pos.is_none() && args.light_int.is_none() {
+            scene.lights.remove(0);
+        }
+        scene.ambient = Color::new(0.0, 0.0, 0.0);
+        scene.lights.push(Box::new(baked.clone()));
+        scene.lights.push(Box::new(sun.clone()));
+        scene.background = Background::Physical(PhysicalSky { sky: baked, sun });
+    }
+
+    scene
+}
+
+// Everything accumulated while rendering, per pixel
+struct RenderState {
+    film: Film,
+    stats: Vec<PixelStats>,
+    features: Option<Features>,
+}
+
+impl RenderState {
+    // `features`: collect denoiser guides
+    fn new(args: &Args, features: bool) -> Self {
+        let (w, h) = (args.width as usize, args.height as usize);
+        Self {
+            film: Film::new(w, h, args.filter),
+            stats: vec![PixelStats::default(); w * h],
+            features: features.then(|| Features::new(w, h)),
+        }
+    }
+
+    // Header: magic, version, settings fingerprint, size, whether denoiser
+    // features follow. The samplers are pure functions of the pixel, sample
+    // index and seed, so the per-pixel sample counts are all the RNG state
+    // there is.
+    fn save_checkpoint(&self, path: &str, fingerprint: u64) {
+        let (w, h) = (self.film.width, self.film.height);
+        let mut out = checkpoint::MAGIC.to_vec();
+        put_u32(&mut out, checkpoint::VERSION);
+        put_u64(&mut out, fingerprint);
+        put_u32(&mut out, w as u32);
+        put_u32(&mut out, h as u32);
+        put_u32(&mut out, self.features.is_some() as u32);
+        let full = Rect::full(w, h);
+        self.film.save_rect(full, &mut out);
+        for s in &self.stats {
+            s.save(&mut out);
+        }
+        if let Some(f) = &self.features {
+            f.save_rect(full, &mut out);
+        }
+        if let Err(e) = checkpoint::write_atomic(path, &out) {
+            eprintln!("warning: could not write checkpoint {path}: {e}");
+        }
+    }
+
+    fn load_checkpoint(args: &Args, path: &str) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        let mut r = Reader::new(&data);
+        if &r.bytes::<4>()? != checkpoint::MAGIC || r.u32()? != checkpoint::VERSION {
+            return Err(format!("{path}: not a checkpoint, or from another version"));
+        }
+        let mut state = RenderState::new(args, args.denoise.is_some());
+        let (w, h) = (state.film.width, state.film.height);
+        if r.u64()? != args.fingerprint || r.u32()? as usize != w || r.u32()? as usize != h {
+            return Err(format!("{path}: saved with a different scene or settings"));
+        }
+        match (r.u32()? != 0, state.features.is_some()) {
+            (true, false) => state.features = Some(Features::new(w, h)),
+            (false, true) => {
+                return Err(format!(
+                    "{path}: saved without --denoise, so it cannot be added now"
+                ))
+            }
+            _ => {}
+        }
+        let full = Rect::full(w, h);
+        state.film.add_rect(full, &mut r)?;
+        for s in &mut state.stats {
+            *s = PixelStats::load(&mut r)?;
+        }
+        if let Some(f) = &mut state.features {
+            f.load_rect(full, &mut r)?;
+        }
+        if !r.is_empty() {
+            return Err(format!("{path}: unexpected data after the render state"));
+        }
+        Ok(state)
+    }
+
+    fn checkpoint_if_due(&self, args: &Args, last: &mut Instant) {
+        if let Some(path) = &args.checkpoint {
+            if last.elapsed().as_secs_f64() >= args.checkpoint_every {
+                self.save_checkpoint(path, args.fingerprint);
+                *last = Instant::now();
+            }
+        }
+    }
+
+    fn stats(&self, i: usize, j: usize) -> &PixelStats {
+        &self.stats[j * self.film.width + i]
+    }
+
+    // Takes the next sample of pixel (i, j)
+    fn sample(&mut self, i: usize, j: usize, scene: &Scene, sampler: &mut dyn Sampler) {
+        let (w, h) = (self.film.width, self.film.height);
+        let idx = j * w + i;
+        sampler.start_sample(i as u32, j as u32, self.stats[idx].count());
+        let (du, dv) = sampler.next_2d();
+        let u = (i as f64 + du) / (w - 1) as f64;
+        let v = (j as f64 + dv) / (h - 1) as f64;
+        let r = scene.cam.get_ray(u, v);
+        stats::count_ray(RayKind::Primary);
+        let color = ray_color(&r, scene, MAX_DEPTH, None, sampler);
+        self.film.add_sample(i as f64 + du, j as f64 + dv, color);
+        self.stats[idx].add(color);
+        if let Some(f) = &mut self.features {
+            let (albedo, normal) = primary_features(&r, scene);
+            f.add(i, j, albedo, normal);
+        }
+    }
+
+    // Finishes every pixel of `rect`, rows top to bottom; `on_row` runs after
+    // each row with the number of rows done
+    fn render_rect(
+        &mut self,
+        rect: Rect,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        adaptive: &AdaptiveOpts,
+        mut on_row: impl FnMut(&Self, usize),
+    ) {
+        for (row, j) in (rect.y0..rect.y1).rev().enumerate() {
+            for i in rect.x0..rect.x1 {
+                while !adaptive.converged(self.stats(i, j)) {
+                    self.sample(i, j, scene, sampler);
+                }
+            }
+            on_row(self, row + 1);
+        }
+    }
+
+    // Renders `tile` on its own and returns it as sent to the coordinator:
+    // the counters and busy time it took, the film over every pixel its
+    // samples reached, then its pixel stats and features. Leaves the tile
+    // cleared, so the next one starts clean.
+    fn render_tile(
+        &mut self,
+        tile: Rect,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+        adaptive: &AdaptiveOpts,
+    ) -> Vec<u8> {
+        let before = stats::Counters::current();
+        let start = Instant::now();
+        self.render_rect(tile, scene, sampler, adaptive, |_, _| {});
+        let mut out = Vec::new();
+        stats::Counters::current().since(&before).save(&mut out);
+        put_u64(&mut out, start.elapsed().as_nanos() as u64);
+        self.film.save_rect(self.film.splat_rect(tile), &mut out);
+        for (i, j) in tile.pixels() {
+            self.stats(i, j).save(&mut out);
+        }
+        if let Some(f) = &self.features {
+            f.save_rect(tile, &mut out);
+        }
+        self.clear_tile(tile);
+        out
+    }
+
+    // Adds a `render_tile` result in; returns its busy time in seconds. The
+    // size is checked first so a bad result leaves the state untouched.
+    fn merge_tile(&mut self, tile: Rect, bytes: &[u8]) -> Result<f64, String> {
+        let reach = self.film.splat_rect(tile);
+        let size = self.tile_bytes(tile);
+        if bytes.len() != size {
+            return Err(format!("{} bytes, expected {size}", bytes.len()));
+        }
+        let mut r = Reader::new(bytes);
+        let counters = stats::Counters::load(&mut r)?;
+        let nanos = r.u64()?;
+        self.film.add_rect(reach, &mut r)?;
+        for (i, j) in tile.pixels() {
+            self.stats[j * self.film.width + i] = PixelStats::load(&mut r)?;
+        }
+        if let Some(f) = &mut self.features {
+            f.load_rect(tile, &mut r)?;
+        }
+        counters.add_to_totals();
+        Ok(nanos as f64 * 1e-9)
+    }
+
+    // Size of a `render_tile` result
+    fn tile_bytes(&self, tile: Rect) -> usize {
+        let reach = self.film.splat_rect(tile);
+        let area = |r: Rect| (r.x1 - r.x0) * (r.y1 - r.y0);
+        let feature_bytes = match self.features {
+            Some(_) => Features::SAVED_PIXEL_BYTES,
+            None => 0,
+        };
+        stats::Counters::SAVED_BYTES
+            + 8
+            + Film::SAVED_PIXEL_BYTES * area(reach)
+            + (PixelStats::SAVED_BYTES + feature_bytes) * area(tile)
+    }
+
+    fn clear_tile(&mut self, tile: Rect) {
+        self.film.clear_rect(self.film.splat_rect(tile));
+        for (i, j) in tile.pixels() {
+            self.stats[j * self.film.width + i] = PixelStats::default();
+        }
+        if let Some(f) = &mut self.features {
+            f.clear_rect(tile);
+        }
+    }
+
+    // Pixels to render so that every pixel of the region gets the same
+    // samples, and when denoising the same neighbors, as in a full render
+    fn render_area(&self, args: &Args) -> Rect {
+        let (w, h) = (self.film.width, self.film.height);
+        let guard = args.denoise.map_or(0, |opts| opts.radius.max(1));
+        self.film.splat_rect(region(args).grow(guard, w, h))
+    }
+
+    // Average samples per pixel over `rect`
+    fn mean_spp(&self, rect: Rect) -> f64 {
+        let total: u64 = rect
+            .pixels()
+            .map(|(i, j)| self.stats(i, j).count() as u64)
+            .sum();
+        total as f64 / (rect.width() * rect.height()) as f64
+    }
+
+    // Writes the image as it stands to --out (and --noisy-out when denoising)
+    fn write(&self, args: &Args) {
+        let (w, h) = (self.film.width, self.film.height);
+        let black = Color::new(0.0, 0.0, 0.0);
+        let mut pixels: Vec<Color> = (0..w * h)
+            .map(|idx| self.film.pixel(idx % w, idx / w))
+            .collect();
+        if let (Some(opts), Some(f)) = (args.denoise, &self.features) {
+            if let Some(path) = &args.noisy_out {
+                let (ow, oh, noisy) = framed(args, &pixels, black);
+                write_ppm(path, ow, oh, &noisy, &args.display);
+            }
+            // Just the region and the neighbors the filter looks at
+            let area = region(args).grow(opts.radius.max(1), w, h);
+            let noisy: Vec<Color> = area.pixels().map(|(i, j)| pixels[j * w + i]).collect();
+            let variances: Vec<f64> = area
+                .pixels()
+                .map(|(i, j)| self.stats(i, j).mean_variance())
+                .collect();
+            let denoised = denoise::denoise(&noisy, &variances, &f.crop(area), opts);
+            for ((i, j), c) in area.pixels().zip(denoised) {
+                pixels[j * w + i] = c;
+            }
+        }
+        let (ow, oh, pixels) = framed(args, &pixels, black);
+        write_ppm(&args.out, ow, oh, &pixels, &args.display);
+        if let Some(path) = &args.buffer_out {
+            let region = region(args);
+            let counts: Vec<u32> = region
+                .pixels()
+                .map(|(i, j)| self.stats(i, j).count())
+                .collect();
+            if let Err(e) = merge::write_buffer(path, &self.film, region, &counts) {
+                eprintln!("warning: could not write buffer {path}: {e}");
+            }
+        }
+    }
+}
+
+// The pixels --region covers, or the whole frame
+fn region(args: &Args) -> Rect {
+    args.region
+        .unwrap_or(Rect::full(args.width as usize, args.height as usize))
+}
+
+// The part of a full-frame buffer (rows bottom to top) that goes in the
+// output: the region, or with --full-frame everything, `blank` outside it
+fn framed<T: Copy>(args: &Args, values: &[T], blank: T) -> (usize, usize, Vec<T>) {
+    let (w, h) = (args.width as usize, args.height as usize);
+    let region = region(args);
+    if args.full_frame {
+        let out = (0..w * h)
+            .map(|idx| match region.contains(idx % w, idx / w) {
+                true => values[idx],
+                false => blank,
+            })
+            .collect();
+        (w, h, out)
+    } else {
+        let out = region.pixels().map(|(i, j)| values[j * w + i]).collect();
+        (region.width(), region.height(), out)
+    }
+}
+
+// Without --noise-threshold every pixel takes exactly --spp samples
+fn adaptive_opts(args: &Args) -> AdaptiveOpts {
+    args.adaptive.unwrap_or(AdaptiveOpts {
+        threshold: 0.0,
+        min_spp: args.samples_per_pixel as u32,
+        max_spp: args.samples_per_pixel as u32,
+    })
+}
+
+// --worker: renders tiles for one coordinator at a time, until killed
+fn run_worker(args: &Args, scene: &Scene, addr: &str) {
+    let listener = match TcpListener::bind(addr) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("error: cannot listen on {addr}: {e}");
+            std::process::exit(1);
+        }
+    };
+    eprintln!("worker: listening on {addr}");
+    for stream in listener.incoming() {
+        let result = stream.and_then(|s| serve_coordinator(args, scene, s));
+        match result {
+            Ok(n) => eprintln!("worker: rendered {n} tiles"),
+            Err(e) => eprintln!("worker: session ended: {e}"),
         }
     }
 }
 
+fn serve_coordinator(args: &Args, scene: &Scene, mut stream: TcpStream) -> std::io::Result<usize> {
+    // Frames are small and strictly request/response; don't let Nagle hold them
+    stream.set_nodelay(true)?;
+    // A coordinator that went away without closing the connection (a crash
+    // or reboot elsewhere on the network) would otherwise block us for good
+    stream.set_read_timeout(Some(net::TIMEOUT))?;
+    let (fingerprint, features) = net::parse_hello(&net::recv_hello(&mut stream)?)?;
+    let ok = fingerprint == args.fingerprint;
+    net::send_frame(&mut stream, &(ok as u32).to_le_bytes())?;
+    if !ok {
+        return Err(std::io::Error::other(
+            "coordinator has a different scene or settings",
+        ));
+    }
+    let adaptive = adaptive_opts(args);
+    let mut sampler = args.sampler.create(adaptive.max_spp, args.seed);
+    let mut state = RenderState::new(args, features);
+    let mut tiles = 0;
+    loop {
+        let tile = match net::parse_request(&net::recv_request(&mut stream)?)? {
+            net::Request::Tile(tile) => tile,
+            net::Request::Heartbeat => continue,
+            net::Request::Done => return Ok(tiles),
+        };
+        if tile.x1 > state.film.width || tile.y1 > state.film.height {
+            return Err(std::io::Error::other("tile outside the frame"));
+        }
+        let out = net::with_heartbeats(&stream, || {
+            state.render_tile(tile, scene, sampler.as_mut(), &adaptive)
+        })?;
+        net::send_frame(&mut stream, &out)?;
+        tiles += 1;
+    }
+}
+
+// --workers: farms tiles out and merges them in tile order, so the result
+// doesn't depend on which worker finished first. Tiles no worker could
+// render are done locally. Returns the time spent rendering, summed over
+// the workers.
+fn render_distributed(
+    args: &Args,
+    scene: &Scene,
+    state: &mut RenderState,
+    progress: &mut Progress,
+) -> f64 {
+    let tiles = net::split_tiles(state.render_area(args), args.tile_size);
+    let sizes: Vec<usize> = tiles.iter().map(|&t| state.tile_bytes(t)).collect();
+    let hello = net::hello(args.fingerprint, state.features.is_some());
+    let results = net::distribute(&args.workers, &tiles, &sizes, &hello, |done| {
+        progress.update(done as f64 / tiles.len() as f64)
+    });
+    let adaptive = adaptive_opts(args);
+    let mut sampler = args.sampler.create(adaptive.max_spp, args.seed);
+    // Tiles rendered here go through the same steps as on a worker, so the
+    // sums come out the same either way
+    let mut scratch = None;
+    let mut busy = 0.0;
+    let mut local = 0;
+    for (tile, result) in tiles.iter().zip(results) {
+        if let Some(bytes) = result {
+            match state.merge_tile(*tile, &bytes) {
+                Ok(secs) => {
+                    busy += secs;
+                    continue;
+                }
+                Err(e) => eprintln!("warning: bad result for tile {tile:?}: {e}"),
+            }
+        }
+        let scratch =
+            scratch.get_or_insert_with(|| RenderState::new(args, state.features.is_some()));
+        let bytes = scratch.render_tile(*tile, scene, sampler.as_mut(), &adaptive);
+        busy += state.merge_tile(*tile, &bytes).expect("local tile");
+        local += 1;
+    }
+    if local > 0 {
+        eprintln!("{local} of {} tiles rendered locally", tiles.len());
+    }
+    busy
+}
+
 fn main() {
-    let max_depth = 5;
+    let argv: Vec<String> = std::env::args().collect();
+    if argv.get(1).map(String::as_str) == Some("merge") {
+        if let Err(e) = merge::run(&argv[2..]) {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if argv.get(1).map(String::as_str) == Some("diff") {
+        // 0: within the thresholds, 1: not, 2: couldn't compare
+        match diff::run(&argv[2..]) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("error: {e}");
+                std::process::exit(2);
+            }
+        }
+    }
     let args = parse_args();
     eprintln!(
         "DEBUG: spheres={} planes={} cubes={} cylinders={}  cam? {}  light? {}",
@@ -620,26 +1991,223 @@ fn main() {
         args.cam.lookfrom.is_some() as u8,
         args.light_pos.is_some() as u8
     );
-    let Scene { world, light, cam } = build_scene(&args);
-
-    // Output
-    let file = File::create(&args.out).expect("Failed to create file");
-    let mut w = BufWriter::new(file);
-    writeln!(w, "P3").unwrap();
-    writeln!(w, "{} {}", args.width, args.height).unwrap();
-    writeln!(w, "255").unwrap();
+    let scene = build_scene(&args);
+    if let Some(addr) = &args.worker {
+        run_worker(&args, &scene, addr);
+        return;
+    }
 
     // Render
-    for j in (0..args.height).rev() {
-        for i in 0..args.width {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _s in 0..args.samples_per_pixel {
-                let u = (i as f64 + math::random_f64()) / (args.width - 1) as f64;
-                let v = (j as f64 + math::random_f64()) / (args.height - 1) as f64;
-                let r = cam.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, &light, max_depth);
+    let features = args.denoise.is_some();
+    // Workers start every tile from zero samples, so they can't continue a
+    // checkpoint or render in passes
+    let mut resume = args.resume;
+    if !args.workers.is_empty() {
+        if resume {
+            eprintln!("warning: --resume is not supported with --workers, starting from scratch");
+            resume = false;
+        }
+        if args.progressive.is_some() {
+            eprintln!("warning: --progressive and --time-limit are ignored with --workers");
+        }
+    }
+    let mut state = match (&args.checkpoint, resume) {
+        (Some(path), true) if std::path::Path::new(path).exists() => {
+            match RenderState::load_checkpoint(&args, path) {
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("error: cannot resume: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        (Some(path), true) => {
+            eprintln!("warning: no checkpoint at {path} yet, starting from scratch");
+            RenderState::new(&args, features)
+        }
+        (None, true) => {
+            eprintln!("warning: --resume needs --checkpoint=<path>, starting from scratch");
+            RenderState::new(&args, features)
+        }
+        _ => RenderState::new(&args, features),
+    };
+    let mut last_checkpoint = Instant::now();
+    let area = state.render_area(&args);
+    let adaptive = adaptive_opts(&args);
+    let mut sampler = args.sampler.create(adaptive.max_spp, args.seed);
+    let mut progress = Progress::new(args.time_limit);
+    let mut fraction = 1.0;
+    let mut render_secs = None;
+    match args.progressive {
+        _ if !args.workers.is_empty() => {
+            render_secs = Some(render_distributed(&args, &scene, &mut state, &mut progress));
+        }
+        None => {
+            state.render_rect(area, &scene, sampler.as_mut(), &adaptive, |state, rows| {
+                progress.update(rows as f64 / area.height() as f64);
+                state.checkpoint_if_due(&args, &mut last_checkpoint);
+            });
+        }
+        Some(every) => {
+            // One sample per unconverged pixel per pass, top to bottom
+            let mut last_snapshot = Instant::now();
+            // After a resume, pick up at the first pass a pixel still needs;
+            // the checkpoint may cover a smaller --region than this run
+            let done = area
+                .pixels()
+                .map(|(i, j)| state.stats(i, j))
+                .filter(|s| !adaptive.converged(s))
+                .map(|s| s.count())
+                .min()
+                .unwrap_or(adaptive.max_spp);
+            let passes = adaptive.max_spp as f64;
+            'passes: for pass in done + 1..=adaptive.max_spp {
+                for (row, j) in (area.y0..area.y1).rev().enumerate() {
+                    for i in area.x0..area.x1 {
+                        // Pixels a time limit or crash left a pass behind catch up
+                        while state.stats(i, j).count() < pass
+                            && !adaptive.converged(state.stats(i, j))
+                        {
+                            state.sample(i, j, &scene, sampler.as_mut());
+                        }
+                    }
+                    fraction =
+                        ((pass - 1) as f64 + (row + 1) as f64 / area.height() as f64) / passes;
+                    progress.update(fraction);
+                    if progress.out_of_time() {
+                        break 'passes;
+                    }
+                }
+                let finished = area
+                    .pixels()
+                    .all(|(i, j)| adaptive.converged(state.stats(i, j)));
+                if finished || pass == adaptive.max_spp {
+                    fraction = 1.0;
+                    break;
+                }
+                state.checkpoint_if_due(&args, &mut last_checkpoint);
+                let due = match every {
+                    Snapshots::Seconds(secs) => last_snapshot.elapsed().as_secs_f64() >= secs,
+                    Snapshots::Passes(n) => pass.is_multiple_of(n),
+                };
+                if due {
+                    state.write(&args);
+                    last_snapshot = Instant::now();
+                }
             }
-            write_color(&mut w, pixel_color, args.samples_per_pixel);
         }
     }
+    progress.finish(fraction);
+
+    let region = region(&args);
+    let report = stats::Report::collect(
+        region.width(),
+        region.height(),
+        render_secs.unwrap_or(progress.elapsed()),
+    );
+    report.print();
+    if let Some(path) = &args.stats_json {
+        if let Err(e) = std::fs::write(path, report.to_json()) {
+            eprintln!("warning: could not write stats {path}: {e}");
+        }
+    }
+    if fraction < 1.0 {
+        eprintln!(
+            "time limit reached: {:.1} samples per pixel on average",
+            state.mean_spp(region)
+        );
+    }
+    if args.adaptive.is_some() {
+        eprintln!(
+            "adaptive sampling: {:.1} samples per pixel on average",
+            state.mean_spp(region)
+        );
+    }
+    if let Some(path) = &args.heatmap {
+        // Without --noise-threshold pixels only fall short of --spp where a
+        // --time-limit stopped the render, so the scale starts at zero
+        let (lo, hi) = match args.adaptive {
+            Some(opts) => (opts.min_spp, opts.max_spp),
+            None => (0, args.samples_per_pixel as u32),
+        };
+        let sample_counts: Vec<u32> = state.stats.iter().map(|s| s.count()).collect();
+        let (w, h, sample_counts) = framed(&args, &sample_counts, 0);
+        if let Err(e) = adaptive::write_heatmap(path, w, h, &sample_counts, lo, hi) {
+            eprintln!("warning: could not write heatmap {path}: {e}");
+        }
+    }
+    if let Some(path) = &args.checkpoint {
+        state.save_checkpoint(path, args.fingerprint);
+    }
+
+    // Output
+    state.write(&args);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A rough metal floor under a spherical lamp, in a black environment, so
+    // all light reaching the camera is direct
+    fn glossy_scene() -> Scene {
+        let mut floor = Material::new(Color::new(0.9, 0.8, 0.7), 0.0);
+        floor.principled = Some(Principled {
+            metallic: 1.0,
+            roughness: 0.4,
+            ..Principled::default()
+        });
+        let mut lamp = Material::new(Color::new(0.0, 0.0, 0.0), 0.0);
+        lamp.emission = Color::new(5.0, 5.0, 5.0);
+        let lamp: Arc<dyn Shape> = Arc::new(Sphere::with_material(
+            Point3::new(0.0, 1.0, -1.0),
+            0.3,
+            lamp,
+        ));
+        let mut world = HittableList::new();
+        world.add(Box::new(Plane::with_material(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            floor,
+        )));
+        world.add(Box::new(lamp.clone()));
+        let black = EnvMap::new(1, 1, vec![Color::new(0.0, 0.0, 0.0)], 0.0, 1.0);
+        Scene {
+            world,
+            lights: vec![Box::new(AreaLight::new(lamp))],
+            ambient: Color::new(0.0, 0.0, 0.0),
+            background: Background::Env(Arc::new(black)),
+            cam: Camera::new(
+                Point3::new(0.0, 1.0, 1.0),
+                Point3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                60.0,
+                1.0,
+            ),
+        }
+    }
+
+    // Mean luminance seen along a ray that catches the lamp's highlight
+    fn mean_luminance(scene: &Scene, depth: i32) -> f64 {
+        const N: u32 = 8192;
+        let r = Ray::new(Point3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        let mut sampler = SamplerKind::Sobol.create(N, 1);
+        let mut sum = 0.0;
+        for i in 0..N {
+            sampler.start_sample(0, 0, i);
+            sum += math::luminance(ray_color(&r, scene, depth, None, sampler.as_mut()));
+        }
+        sum / N as f64
+    }
+
+    #[test]
+    fn glossy_direct_light_is_the_same_at_the_last_bounce() {
+        let scene = glossy_scene();
+        let last = mean_luminance(&scene, 1);
+        let deeper = mean_luminance(&scene, 5);
+        assert!(
+            (last - deeper).abs() < 0.02 * deeper,
+            "depth 1: {last}, depth 5: {deeper}"
+        );
+    }
 }
@@ -0,0 +1,289 @@
+use crate::hittable::HitRecord;
+use crate::math::{cosine_sample_hemisphere, luminance, orthonormal_basis, Color, Vec3};
+use crate::principled::Principled;
+use crate::texture::{SolidColor, Texture};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct BsdfSample {
+    pub wi: Vec3,
+    pub f: Color,
+    pub pdf: f64, // solid-angle density of wi
+}
+
+/// A single scattering lobe. Directions all point away from the surface;
+/// `n` is the shading normal on the side of `wo`.
+pub trait Bxdf {
+    fn eval(&self, wo: Vec3, wi: Vec3, n: Vec3) -> Color;
+    fn pdf(&self, wo: Vec3, wi: Vec3, n: Vec3) -> f64;
+    fn sample(&self, wo: Vec3, n: Vec3, u: (f64, f64)) -> Option<BsdfSample>;
+}
+
+pub struct Lambertian {
+    pub albedo: Color,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Bxdf for Lambertian {
+    fn eval(&self, _wo: Vec3, wi: Vec3, n: Vec3) -> Color {
+        if Vec3::dot(wi, n) > 0.0 {
+            self.albedo / PI
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn pdf(&self, _wo: Vec3, wi: Vec3, n: Vec3) -> f64 {
+        f64::max(0.0, Vec3::dot(wi, n)) / PI
+    }
+
+    fn sample(&self, wo: Vec3, n: Vec3, u: (f64, f64)) -> Option<BsdfSample> {
+        let wi = cosine_sample_hemisphere(n, u);
+        let pdf = self.pdf(wo, wi, n);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi, n),
+            pdf,
+        })
+    }
+}
+
+/// Normalized Blinn-Phong specular lobe: `specular * (n + 8) / 8pi * cos^n(theta_h)`.
+pub struct BlinnPhong {
+    pub specular: Color,
+    pub shininess: f64,
+}
+
+impl BlinnPhong {
+    pub fn new(specular: Color, shininess: f64) -> Self {
+        Self {
+            specular,
+            shininess,
+        }
+    }
+
+    fn half_vector(wo: Vec3, wi: Vec3) -> Option<Vec3> {
+        let h = wo + wi;
+        (h.length_squared() > 0.0).then(|| h.unit())
+    }
+}
+
+impl Bxdf for BlinnPhong {
+    fn eval(&self, wo: Vec3, wi: Vec3, n: Vec3) -> Color {
+        let Some(h) = Self::half_vector(wo, wi) else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        if Vec3::dot(wi, n) <= 0.0 || Vec3::dot(wo, n) <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let cos_h = f64::max(0.0, Vec3::dot(n, h));
+        self.specular * ((self.shininess + 8.0) / (8.0 * PI) * cos_h.powf(self.shininess))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, n: Vec3) -> f64 {
+        let Some(h) = Self::half_vector(wo, wi) else {
+            return 0.0;
+        };
+        let cos_h = Vec3::dot(n, h);
+        let wo_h = Vec3::dot(wo, h);
+        if Vec3::dot(wi, n) <= 0.0 || cos_h <= 0.0 || wo_h <= 0.0 {
+            return 0.0;
+        }
+        // Half vectors are drawn with density (n + 1) / 2pi * cos^n
+        (self.shininess + 1.0) / (2.0 * PI) * cos_h.powf(self.shininess) / (4.0 * wo_h)
+    }
+
+    fn sample(&self, wo: Vec3, n: Vec3, u: (f64, f64)) -> Option<BsdfSample> {
+        let cos_h = u.0.powf(1.0 / (self.shininess + 1.0));
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let (t, b) = orthonormal_basis(n);
+        let h = t * (sin_h * phi.cos()) + b * (sin_h * phi.sin()) + n * cos_h;
+        let wi = h * (2.0 * Vec3::dot(wo, h)) - wo;
+        let pdf = self.pdf(wo, wi, n);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi, n),
+            pdf,
+        })
+    }
+}
+
+/// Lambertian diffuse plus an optional Blinn-Phong highlight. Sampling picks a
+/// lobe in proportion to its reflectance.
+pub struct Phong {
+    pub diffuse: Lambertian,
+    pub specular: BlinnPhong,
+}
+
+impl Phong {
+    pub fn new(albedo: Color, specular: Color, shininess: f64) -> Self {
+        Self {
+            diffuse: Lambertian::new(albedo),
+            specular: BlinnPhong::new(specular, shininess),
+        }
+    }
+
+    // Probability of sampling the specular lobe
+    fn spec_weight(&self) -> f64 {
+        let d = luminance(self.diffuse.albedo);
+        let s = luminance(self.specular.specular);
+        if s <= 0.0 {
+            0.0
+        } else {
+            s / (d + s)
+        }
+    }
+}
+
+impl Bxdf for Phong {
+    fn eval(&self, wo: Vec3, wi: Vec3, n: Vec3) -> Color {
+        self.diffuse.eval(wo, wi, n) + self.specular.eval(wo, wi, n)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, n: Vec3) -> f64 {
+        let w = self.spec_weight();
+        (1.0 - w) * self.diffuse.pdf(wo, wi, n) + w * self.specular.pdf(wo, wi, n)
+    }
+
+    fn sample(&self, wo: Vec3, n: Vec3, u: (f64, f64)) -> Option<BsdfSample> {
+        let w = self.spec_weight();
+        // Reuse u.0 after choosing the lobe so both dimensions stay uniform
+        let wi = if u.0 < w {
+            self.specular.sample(wo, n, (u.0 / w, u.1))?.wi
+        } else {
+            self.diffuse.sample(wo, n, ((u.0 - w) / (1.0 - w), u.1))?.wi
+        };
+        let pdf = self.pdf(wo, wi, n);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi, n),
+            pdf,
+        })
+    }
+}
+
+/// Surface description shared by every primitive.
+#[derive(Clone)]
+pub struct Material {
+    pub albedo: Arc<dyn Texture>,
+    pub reflectivity: f64,
+    pub emission: Color, // emitted radiance, black for non-emitters
+    pub two_sided: bool, // emit from the back face as well
+    // Blinn-Phong highlight color (black = diffuse only) and exponent
+    pub specular: Color,
+    pub shininess: f64,
+    // Physically based GGX material; replaces the diffuse/Phong/mirror model when set
+    pub principled: Option<Principled>,
+    // Tangent-space normal map (RGB in [0,1] -> [-1,1], +Z = unperturbed)
+    pub normal_map: Option<Arc<dyn Texture>>,
+    // Height map; the luminance times `bump_scale` is the displacement in world units
+    pub bump_map: Option<Arc<dyn Texture>>,
+    pub bump_scale: f64,
+}
+
+impl Material {
+    pub fn new(albedo: Color, reflectivity: f64) -> Self {
+        Self {
+            albedo: Arc::new(SolidColor::new(albedo)),
+            reflectivity,
+            emission: Color::new(0.0, 0.0, 0.0),
+            two_sided: false,
+            specular: Color::new(0.0, 0.0, 0.0),
+            shininess: 32.0,
+            principled: None,
+            normal_map: None,
+            bump_map: None,
+            bump_scale: 0.02,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.x > 0.0 || self.emission.y > 0.0 || self.emission.z > 0.0
+    }
+
+    /// Replaces `rec.normal` with the normal- or bump-mapped shading normal.
+    /// `rec.geometric_normal` is left untouched.
+    pub fn perturb_normal(&self, rec: &mut HitRecord) {
+        if self.normal_map.is_none() && self.bump_map.is_none() {
+            return;
+        }
+        // Work in the outward-facing frame so tangent space matches the UV layout
+        let n = if rec.front_face {
+            rec.geometric_normal
+        } else {
+            -rec.geometric_normal
+        };
+        let mut dpdu = rec.dpdu;
+        let mut dpdv = rec.dpdv;
+        if Vec3::cross(dpdu, dpdv).length_squared() < 1e-20 {
+            // Degenerate parameterization (e.g. sphere poles): any frame will do
+            let (t, b) = orthonormal_basis(n);
+            dpdu = t;
+            dpdv = b;
+        }
+
+        let mut shading = n;
+        if let Some(bump) = &self.bump_map {
+            // Finite differences of the height field, displacing along n
+            const DELTA: f64 = 1e-3;
+            let height = |du: f64, dv: f64| {
+                let p = rec.p + dpdu * du + dpdv * dv;
+                luminance(bump.value(rec.u + du, rec.v + dv, p)) * self.bump_scale
+            };
+            let h = height(0.0, 0.0);
+            let dhdu = (height(DELTA, 0.0) - h) / DELTA;
+            let dhdv = (height(0.0, DELTA) - h) / DELTA;
+            let bumped = Vec3::cross(dpdu + n * dhdu, dpdv + n * dhdv);
+            if bumped.length_squared() > 0.0 {
+                shading = bumped.unit();
+                if Vec3::dot(shading, n) < 0.0 {
+                    shading = -shading;
+                }
+            }
+        }
+        if let Some(map) = &self.normal_map {
+            let c = map.value(rec.u, rec.v, rec.p);
+            let local = Vec3::new(2.0 * c.x - 1.0, 2.0 * c.y - 1.0, 2.0 * c.z - 1.0);
+            // Gram-Schmidt tangent frame around the (possibly bumped) normal
+            let t = dpdu - shading * Vec3::dot(dpdu, shading);
+            if t.length_squared() > 0.0 && local.length_squared() > 0.0 {
+                let t = t.unit();
+                let mut b = Vec3::cross(shading, t);
+                if Vec3::dot(b, dpdv) < 0.0 {
+                    b = -b;
+                }
+                shading = (t * local.x + b * local.y + shading * local.z).unit();
+            }
+        }
+
+        // Never let the shading normal swing past the surface
+        if Vec3::dot(shading, n) <= 1e-4 {
+            return;
+        }
+        rec.normal = if rec.front_face { shading } else { -shading };
+    }
+
+    /// Radiance leaving the surface, seen from the front or the back face.
+    pub fn emitted(&self, front_face: bool) -> Color {
+        if front_face || self.two_sided {
+            self.emission
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+}
@@ -1,8 +1,3 @@
-use rand::Rng;
-pub fn random_f64() -> f64 {
-    // in [0,1)
-    rand::thread_rng().gen::<f64>()
-}
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Vec3 {
     pub x: f64,
@@ -107,3 +102,39 @@ impl Neg for Vec3 {
         Vec3::new(-self.x, -self.y, -self.z)
     }
 }
+
+/// Rec. 709 relative luminance of a linear RGB color.
+pub fn luminance(c: Color) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+pub fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    // Veach's power heuristic (beta = 2) for one sample from each strategy
+    let f2 = f_pdf * f_pdf;
+    let g2 = g_pdf * g_pdf;
+    if f2 + g2 == 0.0 {
+        0.0
+    } else {
+        f2 / (f2 + g2)
+    }
+}
+
+/// Two unit vectors that together with `n` form an orthonormal basis.
+pub fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+    // Duff et al. 2017, branchless ONB
+    let sign = 1.0_f64.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    let s = Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+    let t = Vec3::new(b, sign + n.y * n.y * a, -n.y);
+    (s, t)
+}
+
+/// Cosine-weighted direction on the hemisphere around `n`.
+pub fn cosine_sample_hemisphere(n: Vec3, u: (f64, f64)) -> Vec3 {
+    let r = u.0.sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u.1;
+    let (s, t) = orthonormal_basis(n);
+    let z = (1.0 - u.0).max(0.0).sqrt();
+    s * (r * phi.cos()) + t * (r * phi.sin()) + n * z
+}
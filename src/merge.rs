@@ -0,0 +1,303 @@
+use crate::checkpoint::{put_u32, write_atomic, Reader};
+use crate::film::{Film, Rect};
+use crate::filter::Filter;
+use crate::image_io::{load_image, srgb_to_linear};
+use crate::math::Color;
+use crate::tonemap::{DisplayTransform, ToneMap, Transfer};
+
+// Float buffer layout (little-endian): magic, version, frame width and height,
+// the region x0, y0, x1, y1 (rows from the bottom), then the film's weighted
+// sums and weight for each region pixel, then each region pixel's sample count
+const MAGIC: &[u8; 4] = b"RTBF";
+const VERSION: u32 = 1;
+
+/// Writes the `rect` part of the film and `counts` (samples per pixel, in
+/// `rect.pixels()` order) as a float buffer for `rt merge`.
+pub fn write_buffer(path: &str, film: &Film, rect: Rect, counts: &[u32]) -> std::io::Result<()> {
+    write_atomic(path, &buffer_bytes(film, rect, counts))
+}
+
+fn buffer_bytes(film: &Film, rect: Rect, counts: &[u32]) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    put_u32(&mut out, VERSION);
+    for v in [film.width, film.height, rect.x0, rect.y0, rect.x1, rect.y1] {
+        put_u32(&mut out, v as u32);
+    }
+    film.save_rect(rect, &mut out);
+    for &n in counts {
+        put_u32(&mut out, n);
+    }
+    out
+}
+
+const USAGE: &str = "Usage: rt merge [FLAGS] <input>...
+
+Combines partial renders into one image. Inputs are either float buffers
+written with --buffer-out (placed by the region they were rendered with), or
+PNG/PPM images, placed with path@x,y (top-left corner, default 0,0).
+Where inputs overlap they are averaged: float buffers by sample weight, so
+renders with different --seed values add up to one with their total samples;
+images equally, in linear light.
+
+  --out=<file.ppm>            Output file (default merged.ppm)
+  --res=<WxH>                 Frame size for images (default: what the images cover)
+  --exposure=<stops>          Display settings, as for rendering
+  --tonemap=<none|reinhard|aces|agx>
+  --transfer=<srgb|gamma2>
+";
+
+/// `rt merge`: `args` are the arguments after the subcommand.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut out = "merged.ppm".to_string();
+    let mut res: Option<(usize, usize)> = None;
+    let mut display = DisplayTransform::default();
+    let mut inputs = Vec::new();
+    for a in args {
+        if let Some(v) = a.strip_prefix("--out=") {
+            out = v.to_string();
+        } else if let Some(v) = a.strip_prefix("--res=") {
+            res = v
+                .split_once('x')
+                .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                .filter(|&(w, h)| w > 0 && h > 0);
+            if res.is_none() {
+                eprintln!("warning: ignoring --res={v}, expected WxH");
+            }
+        } else if let Some(v) = a.strip_prefix("--exposure=") {
+            if let Ok(v) = v.parse::<f64>() {
+                display.exposure = v;
+            }
+        } else if let Some(v) = a.strip_prefix("--tonemap=") {
+            if let Some(t) = ToneMap::parse(v) {
+                display.tonemap = t;
+            }
+        } else if let Some(v) = a.strip_prefix("--transfer=") {
+            if let Some(t) = Transfer::parse(v) {
+                display.transfer = t;
+            }
+        } else if a == "--help" {
+            eprint!("{USAGE}");
+            return Ok(());
+        } else if a.starts_with("--") {
+            eprintln!("warning: unknown merge flag {a}");
+        } else {
+            inputs.push(a.as_str());
+        }
+    }
+    if inputs.is_empty() {
+        return Err(format!("nothing to merge\n\n{USAGE}"));
+    }
+
+    let mut buffers = Vec::new();
+    let mut images = Vec::new();
+    for input in inputs {
+        // "path@x,y" places an image; a path may itself contain '@'
+        let (path, at) = match input.rsplit_once('@').and_then(|(p, xy)| {
+            let (x, y) = xy.split_once(',')?;
+            Some((p, (x.parse().ok()?, y.parse().ok()?)))
+        }) {
+            Some((path, at)) => (path, Some(at)),
+            None => (input, None),
+        };
+        let bytes = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        if bytes.starts_with(MAGIC) {
+            if at.is_some() {
+                eprintln!("warning: {path}: float buffers are placed by their region, ignoring @");
+            }
+            buffers.push((path, bytes));
+        } else {
+            images.push((path, at.unwrap_or((0, 0))));
+        }
+    }
+    let (w, h, pixels) = match (buffers.is_empty(), images.is_empty()) {
+        (false, false) => return Err("can't merge float buffers with images".into()),
+        (false, true) => merge_buffers(&buffers)?,
+        _ => merge_images(&images, res)?,
+    };
+    crate::write_ppm(&out, w, h, &pixels, &display);
+    eprintln!("wrote {out} ({w}x{h})");
+    Ok(())
+}
+
+// Sums every buffer's film into one; pixels come out rows bottom to top
+fn merge_buffers(buffers: &[(&str, Vec<u8>)]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut film: Option<Film> = None;
+    let mut counts: Vec<u64> = Vec::new();
+    for (path, bytes) in buffers {
+        let err = |e: String| format!("{path}: {e}");
+        let mut r = Reader::new(bytes);
+        r.bytes::<4>().map_err(err)?;
+        if r.u32().map_err(err)? != VERSION {
+            return Err(err("float buffer from another version".into()));
+        }
+        let mut next = || r.u32().map(|v| v as usize).map_err(err);
+        let (w, h) = (next()?, next()?);
+        let region = Rect::new(next()?, next()?, next()?, next()?);
+        let film = film.get_or_insert_with(|| {
+            counts = vec![0; w * h];
+            Film::new(w, h, Filter::default())
+        });
+        if (film.width, film.height) != (w, h) {
+            return Err(err(format!(
+                "{w}x{h} frame, but the first buffer is {}x{}",
+                film.width, film.height
+            )));
+        }
+        if region.x0 > region.x1 || region.y0 > region.y1 {
+            return Err(err("bad region".into()));
+        }
+        film.add_rect(region, &mut r).map_err(err)?;
+        for (i, j) in region.pixels() {
+            counts[j * w + i] += r.u32().map_err(err)? as u64;
+        }
+    }
+    let film = film.expect("at least one buffer");
+    let (w, h) = (film.width, film.height);
+    let covered = counts.iter().filter(|&&n| n > 0).count();
+    let total: u64 = counts.iter().sum();
+    eprintln!(
+        "merged {} buffers: {:.1} samples per pixel on average",
+        buffers.len(),
+        total as f64 / covered.max(1) as f64
+    );
+    if covered < w * h {
+        eprintln!(
+            "warning: {} pixels have no samples and are black",
+            w * h - covered
+        );
+    }
+    let pixels = (0..w * h).map(|idx| film.pixel(idx % w, idx / w)).collect();
+    Ok((w, h, pixels))
+}
+
+// Averages the images in linear light, assuming they're sRGB-encoded
+fn merge_images(
+    images: &[(&str, (usize, usize))],
+    res: Option<(usize, usize)>,
+) -> Result<(usize, usize, Vec<Color>), String> {
+    let loaded = images
+        .iter()
+        .map(|&(path, at)| load_image(path).map(|img| (img, at)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (w, h) = res.unwrap_or_else(|| {
+        loaded.iter().fold((0, 0), |(w, h), (img, (x, y))| {
+            (w.max(x + img.width), h.max(y + img.height))
+        })
+    });
+    let mut sums = vec![Color::new(0.0, 0.0, 0.0); w * h];
+    let mut counts = vec![0u32; w * h];
+    let decode = |c: Color| {
+        Color::new(
+            srgb_to_linear(c.x),
+            srgb_to_linear(c.y),
+            srgb_to_linear(c.z),
+        )
+    };
+    for (img, (x, y)) in &loaded {
+        for row in 0..img.height.min(h.saturating_sub(*y)) {
+            // Images count rows from the top, the output from the bottom
+            let j = h - 1 - (y + row);
+            for col in 0..img.width.min(w.saturating_sub(*x)) {
+                let idx = j * w + x + col;
+                sums[idx] += decode(img.pixels[row * img.width + col]);
+                counts[idx] += 1;
+            }
+        }
+    }
+    let uncovered = counts.iter().filter(|&&n| n == 0).count();
+    if uncovered > 0 {
+        eprintln!("warning: {uncovered} pixels are not covered by any image and are black");
+    }
+    let pixels = sums
+        .iter()
+        .zip(&counts)
+        .map(|(&s, &n)| s / n.max(1) as f64)
+        .collect();
+    Ok((w, h, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A float buffer of `rect` with `n` samples of `c` at every pixel center
+    fn buffer(w: usize, h: usize, rect: Rect, c: Color, n: u32) -> Vec<u8> {
+        let mut film = Film::new(w, h, Filter::default());
+        for (i, j) in rect.pixels() {
+            for _ in 0..n {
+                film.add_sample(i as f64 + 0.5, j as f64 + 0.5, c);
+            }
+        }
+        buffer_bytes(&film, rect, &vec![n; rect.width() * rect.height()])
+    }
+
+    fn near(c: Color, (r, g, b): (f64, f64, f64)) -> bool {
+        (c.x - r).abs() < 1e-9 && (c.y - g).abs() < 1e-9 && (c.z - b).abs() < 1e-9
+    }
+
+    // A flat sRGB-encoded PPM in the temp directory
+    fn ppm(name: &str, w: usize, h: usize, level: u8) -> String {
+        let mut bytes = format!("P6\n{w} {h}\n255\n").into_bytes();
+        bytes.resize(bytes.len() + w * h * 3, level);
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn buffers_are_averaged_by_sample_weight() {
+        // One red sample per pixel on the left three columns, three blue ones
+        // on the right three
+        let red = buffer(4, 2, Rect::new(0, 0, 3, 2), Color::new(1.0, 0.0, 0.0), 1);
+        let blue = buffer(4, 2, Rect::new(1, 0, 4, 2), Color::new(0.0, 0.0, 1.0), 3);
+        let (w, h, px) = merge_buffers(&[("red", red.clone()), ("blue", blue)]).unwrap();
+        assert_eq!((w, h), (4, 2));
+        for row in px.chunks(4) {
+            assert!(near(row[0], (1.0, 0.0, 0.0)));
+            assert!(near(row[1], (0.25, 0.0, 0.75)));
+            assert!(near(row[2], (0.25, 0.0, 0.75)));
+            assert!(near(row[3], (0.0, 0.0, 1.0)));
+        }
+
+        // Pixels no buffer covers stay black
+        let (_, _, px) = merge_buffers(&[("red", red.clone())]).unwrap();
+        assert!(near(px[3], (0.0, 0.0, 0.0)));
+
+        let small = buffer(2, 2, Rect::full(2, 2), Color::new(1.0, 1.0, 1.0), 1);
+        let err = merge_buffers(&[("red", red.clone()), ("small", small)]).unwrap_err();
+        assert_eq!(err, "small: 2x2 frame, but the first buffer is 4x2");
+        let cut = red[..red.len() - 1].to_vec();
+        assert!(merge_buffers(&[("cut", cut)])
+            .unwrap_err()
+            .starts_with("cut: "));
+    }
+
+    #[test]
+    fn images_are_placed_and_averaged_in_linear_light() {
+        let white = ppm("rt_merge_white.ppm", 2, 2, 255);
+        let black = ppm("rt_merge_black.ppm", 2, 2, 0);
+        let (w, h, px) = merge_images(&[(&white, (0, 0)), (&black, (1, 1))], None).unwrap();
+        assert_eq!((w, h), (3, 3));
+        // Output rows run bottom to top, placement counts from the top left
+        let at = |x: usize, y: usize| px[(h - 1 - y) * w + x];
+        assert!(near(at(0, 0), (1.0, 1.0, 1.0)));
+        assert!(near(at(1, 1), (0.5, 0.5, 0.5)));
+        assert!(near(at(2, 2), (0.0, 0.0, 0.0)));
+        assert!(near(at(0, 2), (0.0, 0.0, 0.0)));
+
+        let (w, h, _) = merge_images(&[(&white, (0, 0)), (&black, (1, 1))], Some((2, 2))).unwrap();
+        assert_eq!((w, h), (2, 2));
+
+        // path@x,y on the command line
+        let out = std::env::temp_dir()
+            .join("rt_merge_out.ppm")
+            .display()
+            .to_string();
+        run(&[format!("--out={out}"), format!("{white}@1,0")]).unwrap();
+        let img = load_image(&out).unwrap();
+        assert_eq!((img.width, img.height), (3, 2));
+        assert!(near(img.pixels[0], (0.0, 0.0, 0.0)));
+        assert!(near(img.pixels[1], (1.0, 1.0, 1.0)));
+        assert!(near(img.pixels[5], (1.0, 1.0, 1.0)));
+    }
+}
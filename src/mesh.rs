@@ -0,0 +1,230 @@
+use crate::hittable::{HitRecord, Hittable, Shape};
+use crate::material::Material;
+use crate::math::{Point3, Vec3};
+use crate::ray::Ray;
+use crate::stats::{self, Prim};
+use crate::triangle;
+
+/// Indexed triangle mesh sharing a single material.
+pub struct Mesh {
+    pub vertices: Vec<Point3>,
+    pub faces: Vec<[usize; 3]>,
+    // Texture coordinates, and per face the indices into them (empty if none)
+    pub uvs: Vec<(f64, f64)>,
+    pub face_uvs: Vec<Option<[usize; 3]>>,
+    pub material: Material,
+    area_cdf: Vec<f64>, // running sum of face areas, for light sampling
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Point3>, faces: Vec<[usize; 3]>, material: Material) -> Self {
+        let mut area_cdf = Vec::with_capacity(faces.len());
+        let mut total = 0.0;
+        for f in &faces {
+            let (v0, v1, v2) = (vertices[f[0]], vertices[f[1]], vertices[f[2]]);
+            total += 0.5 * Vec3::cross(v1 - v0, v2 - v0).length();
+            area_cdf.push(total);
+        }
+        Self {
+            vertices,
+            faces,
+            uvs: Vec::new(),
+            face_uvs: Vec::new(),
+            material,
+            area_cdf,
+        }
+    }
+
+    /// Per-vertex texture coordinates; `face_uvs` runs parallel to `faces`.
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>, face_uvs: Vec<Option<[usize; 3]>>) -> Self {
+        self.uvs = uvs;
+        self.face_uvs = face_uvs;
+        self
+    }
+
+    /// Loads the `v`, `vt` and `f` records of a Wavefront OBJ file. Polygons
+    /// are fan-triangulated; normal indices and everything else are ignored.
+    pub fn load_obj(path: &str, material: Material) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let mut vertices = Vec::new();
+        let mut uvs = Vec::new();
+        let mut faces = Vec::new();
+        let mut face_uvs = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let mut it = line.split_whitespace();
+            match it.next() {
+                Some("v") => {
+                    let c: Vec<f64> = it.take(3).filter_map(|t| t.parse().ok()).collect();
+                    if c.len() != 3 {
+                        return Err(format!("{path}:{}: bad vertex", lineno + 1));
+                    }
+                    vertices.push(Point3::new(c[0], c[1], c[2]));
+                }
+                Some("vt") => {
+                    // v is optional and defaults to 0
+                    let c: Vec<f64> = it.take(2).map_while(|t| t.parse().ok()).collect();
+                    if c.is_empty() {
+                        return Err(format!("{path}:{}: bad texture coordinate", lineno + 1));
+                    }
+                    uvs.push((c[0], c.get(1).copied().unwrap_or(0.0)));
+                }
+                Some("f") => {
+                    let bad = || format!("{path}:{}: bad face", lineno + 1);
+                    let out_of_range = || format!("{path}:{}: index out of range", lineno + 1);
+                    // Negative indices count back from the end
+                    let resolve = |s: &str, len: usize| -> Result<usize, String> {
+                        let i: i64 = s.parse().map_err(|_| bad())?;
+                        let i = if i < 0 { len as i64 + i } else { i - 1 };
+                        if i < 0 || i as usize >= len {
+                            return Err(out_of_range());
+                        }
+                        Ok(i as usize)
+                    };
+                    let mut idx = Vec::new();
+                    let mut tex = Vec::new();
+                    for t in it {
+                        // "7", "7/1", "7//3", "7/1/3"
+                        let mut parts = t.split('/');
+                        idx.push(resolve(parts.next().unwrap_or(""), vertices.len())?);
+                        match parts.next() {
+                            Some(s) if !s.is_empty() => tex.push(resolve(s, uvs.len())?),
+                            _ => {}
+                        }
+                    }
+                    // UVs only count if every corner has one
+                    let tex = (tex.len() == idx.len()).then_some(tex);
+                    for k in 1..idx.len().saturating_sub(1) {
+                        faces.push([idx[0], idx[k], idx[k + 1]]);
+                        face_uvs.push(tex.as_ref().map(|t| [t[0], t[k], t[k + 1]]));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if faces.is_empty() {
+            return Err(format!("{path}: no faces"));
+        }
+        Ok(Self::new(vertices, faces, material).with_uvs(uvs, face_uvs))
+    }
+
+    fn face(&self, i: usize) -> (Point3, Point3, Point3) {
+        let f = self.faces[i];
+        (
+            self.vertices[f[0]],
+            self.vertices[f[1]],
+            self.vertices[f[2]],
+        )
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_tests(Prim::Triangle, self.faces.len() as u64);
+        let mut closest = t_max;
+        let mut hit_face = None;
+        for i in 0..self.faces.len() {
+            let (v0, v1, v2) = self.face(i);
+            if let Some((t, b1, b2)) = triangle::intersect(v0, v1, v2, r, t_min, closest) {
+                closest = t;
+                hit_face = Some((i, b1, b2));
+            }
+        }
+        let (i, b1, b2) = hit_face?;
+        let (v0, v1, v2) = self.face(i);
+        let outward = Vec3::cross(v1 - v0, v2 - v0).unit();
+        // Interpolated vertex UVs where the file has them, otherwise per-face
+        // barycentric UVs like `Triangle`
+        let (u, v, dpdu, dpdv) = match self.face_uvs.get(i).copied().flatten() {
+            Some(t) => {
+                let uv = t.map(|k| self.uvs[k]);
+                let b0 = 1.0 - b1 - b2;
+                let (dpdu, dpdv) =
+                    triangle::uv_tangents([v0, v1, v2], uv).unwrap_or((v1 - v0, v2 - v0));
+                (
+                    b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+                    b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+                    dpdu,
+                    dpdv,
+                )
+            }
+            None => (b1, b2, v1 - v0, v2 - v0),
+        };
+        Some(
+            HitRecord::with_face_normal(r, r.at(closest), outward, closest, &self.material)
+                .with_uv(u, v, dpdu, dpdv),
+        )
+    }
+}
+
+impl Shape for Mesh {
+    fn area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+
+    fn sample_surface(&self, u: (f64, f64)) -> (Point3, Vec3) {
+        // Pick a face proportionally to its area and rescale u.0 onto it
+        let target = u.0 * self.area();
+        let i = self
+            .area_cdf
+            .partition_point(|&c| c <= target)
+            .min(self.faces.len() - 1);
+        let lo = if i == 0 { 0.0 } else { self.area_cdf[i - 1] };
+        let width = self.area_cdf[i] - lo;
+        let u0 = if width > 0.0 {
+            ((target - lo) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (v0, v1, v2) = self.face(i);
+        let n = Vec3::cross(v1 - v0, v2 - v0).unit();
+        (triangle::sample(v0, v1, v2, (u0, u.1)), n)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Color;
+
+    fn load(name: &str, obj: &str) -> Result<Mesh, String> {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, obj).unwrap();
+        Mesh::load_obj(
+            path.to_str().unwrap(),
+            Material::new(Color::new(0.5, 0.5, 0.5), 0.0),
+        )
+    }
+
+    #[test]
+    fn vertex_uvs_are_interpolated() {
+        // A 2x1 quad facing +Z with the texture stretched across it
+        let obj = "v 0 0 0\nv 2 0 0\nv 2 1 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                   f 1/1 2/2 3/3 4/4\n";
+        let mesh = load("rt_mesh_uv.obj", obj).unwrap();
+        assert_eq!(mesh.faces.len(), 2);
+        for (x, y) in [(1.5, 0.25), (0.5, 0.75)] {
+            let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = mesh.hit(&r, 0.0, 10.0).unwrap();
+            assert!((rec.u - x / 2.0).abs() < 1e-9 && (rec.v - y).abs() < 1e-9);
+            assert!((rec.dpdu - Vec3::new(2.0, 0.0, 0.0)).length() < 1e-9);
+            assert!((rec.dpdv - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        }
+
+        // Without vt the UVs are barycentric
+        let mesh = load("rt_mesh_no_uv.obj", "v 0 0 0\nv 2 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let r = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, 0.0, 10.0).unwrap();
+        assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.5).abs() < 1e-9);
+
+        let bad = load(
+            "rt_mesh_bad_uv.obj",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1 2/2 3/3\n",
+        );
+        assert!(bad.err().unwrap().ends_with("index out of range"));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::checkpoint::{put_u32, put_u64, Reader};
+use crate::film::Rect;
+
+// Protocol, every message a frame (u64 length + payload, little-endian):
+//   coordinator -> worker  hello: MAGIC, VERSION, settings fingerprint, whether
+//                          to collect denoiser features
+//   worker -> coordinator  u32 1 if the fingerprints match, 0 otherwise
+//   coordinator -> worker  tile: x0, y0, x1, y1 as u32, or DONE when there
+//                          are no more; empty frames as heartbeats while it
+//                          waits on tiles other workers might give back
+//   worker -> coordinator  empty frames as heartbeats while rendering, then
+//                          the tile result
+const MAGIC: &[u8; 4] = b"RTDW";
+const VERSION: u32 = 2;
+const DONE: &[u8; 4] = b"DONE";
+const HELLO_BYTES: usize = 20;
+const TILE_BYTES: usize = 16;
+
+/// How often each side reports that it's alive while the other waits.
+const HEARTBEAT: Duration = Duration::from_secs(1);
+/// A peer silent for this long is given up on: a worker by the coordinator,
+/// or a coordinator by the worker.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn send_frame(stream: &mut TcpStream, payload: &[u8]) -> io::Result<()> {
+    stream.write_all(&(payload.len() as u64).to_le_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+fn read_exact(stream: &mut TcpStream, buf: &mut [u8]) -> io::Result<()> {
+    stream.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => io::Error::other("connection closed"),
+        // How a read timeout shows up, depending on the platform
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            io::Error::other(format!("no response for {} seconds", TIMEOUT.as_secs()))
+        }
+        _ => e,
+    })
+}
+
+/// Reads a frame of at most `max` bytes; anything longer is refused before
+/// allocating, as the length prefix may be garbage.
+pub fn recv_frame(stream: &mut TcpStream, max: usize) -> io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    read_exact(stream, &mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > max as u64 {
+        return Err(io::Error::other(format!(
+            "frame of {len} bytes, expected at most {max}"
+        )));
+    }
+    let mut buf = vec![0; len as usize];
+    read_exact(stream, &mut buf)?;
+    Ok(buf)
+}
+
+pub fn recv_hello(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    recv_frame(stream, HELLO_BYTES)
+}
+
+pub fn recv_request(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    recv_frame(stream, TILE_BYTES)
+}
+
+/// Runs `work`, sending heartbeats on `stream` from another thread meanwhile,
+/// so they keep coming however long the work takes between checks.
+pub fn with_heartbeats<T>(stream: &TcpStream, work: impl FnOnce() -> T) -> io::Result<T> {
+    let mut beats = stream.try_clone()?;
+    let (stop, stopped) = mpsc::channel::<()>();
+    thread::scope(|s| {
+        let beater = s.spawn(move || {
+            while stopped.recv_timeout(HEARTBEAT) == Err(mpsc::RecvTimeoutError::Timeout) {
+                send_frame(&mut beats, &[])?;
+            }
+            Ok(())
+        });
+        let out = work();
+        // Stop beating before the caller writes to the stream again
+        drop(stop);
+        beater.join().unwrap().map(|()| out)
+    })
+}
+
+fn to_io(e: String) -> io::Error {
+    io::Error::other(e)
+}
+
+pub fn hello(fingerprint: u64, features: bool) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    put_u32(&mut out, VERSION);
+    put_u64(&mut out, fingerprint);
+    put_u32(&mut out, features as u32);
+    out
+}
+
+/// Returns the coordinator's fingerprint and whether it wants features.
+pub fn parse_hello(frame: &[u8]) -> io::Result<(u64, bool)> {
+    let mut r = Reader::new(frame);
+    if &r.bytes::<4>().map_err(to_io)? != MAGIC || r.u32().map_err(to_io)? != VERSION {
+        return Err(io::Error::other("not a coordinator, or another version"));
+    }
+    Ok((r.u64().map_err(to_io)?, r.u32().map_err(to_io)? != 0))
+}
+
+/// What the coordinator asks of a worker.
+pub enum Request {
+    Tile(Rect),
+    Heartbeat,
+    Done,
+}
+
+pub fn parse_request(frame: &[u8]) -> io::Result<Request> {
+    if frame.is_empty() {
+        return Ok(Request::Heartbeat);
+    }
+    if frame == DONE {
+        return Ok(Request::Done);
+    }
+    let mut r = Reader::new(frame);
+    let mut v = [0; 4];
+    for x in &mut v {
+        *x = r.u32().map_err(to_io)? as usize;
+    }
+    Ok(Request::Tile(Rect::new(v[0], v[1], v[2], v[3])))
+}
+
+fn tile_frame(tile: Rect) -> Vec<u8> {
+    let mut out = Vec::new();
+    for v in [tile.x0, tile.y0, tile.x1, tile.y1] {
+        put_u32(&mut out, v as u32);
+    }
+    out
+}
+
+/// Square tiles covering `area`, top rows first like a local render.
+pub fn split_tiles(area: Rect, size: usize) -> Vec<Rect> {
+    let size = size.max(1);
+    let mut tiles = Vec::new();
+    let mut y1 = area.y1;
+    while y1 > area.y0 {
+        let y0 = y1.saturating_sub(size).max(area.y0);
+        for x0 in (area.x0..area.x1).step_by(size) {
+            tiles.push(Rect::new(x0, y0, (x0 + size).min(area.x1), y1));
+        }
+        y1 = y0;
+    }
+    tiles
+}
+
+// Tiles waiting for a worker, and how many are out being rendered
+struct Queue {
+    pending: VecDeque<usize>,
+    in_flight: usize,
+}
+
+/// Renders `tiles` on the workers at `addrs`, one thread per worker. A worker
+/// that fails, disconnects or stops sending heartbeats is dropped and its
+/// tile goes back in the queue for the others. `sizes` are the result sizes
+/// in bytes. Returns each tile's result, or `None` for tiles no worker was
+/// left to render. `on_done` gets the number of finished tiles after each one.
+pub fn distribute(
+    addrs: &[String],
+    tiles: &[Rect],
+    sizes: &[usize],
+    hello: &[u8],
+    mut on_done: impl FnMut(usize),
+) -> Vec<Option<Vec<u8>>> {
+    let queue = Mutex::new(Queue {
+        pending: (0..tiles.len()).collect(),
+        in_flight: 0,
+    });
+    let changed = Condvar::new();
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        for addr in addrs {
+            let (queue, changed, tx) = (&queue, &changed, tx.clone());
+            s.spawn(move || {
+                if let Err(e) = serve_worker(addr, tiles, sizes, hello, queue, changed, &tx) {
+                    eprintln!("warning: worker {addr}: {e}; not using it");
+                }
+            });
+        }
+        drop(tx);
+        let mut results = vec![None; tiles.len()];
+        let mut done = 0;
+        for (idx, bytes) in rx {
+            results[idx] = Some(bytes);
+            done += 1;
+            on_done(done);
+        }
+        results
+    })
+}
+
+fn serve_worker(
+    addr: &str,
+    tiles: &[Rect],
+    sizes: &[usize],
+    hello: &[u8],
+    queue: &Mutex<Queue>,
+    changed: &Condvar,
+    tx: &mpsc::Sender<(usize, Vec<u8>)>,
+) -> io::Result<()> {
+    let sock = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other("address did not resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&sock, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_nodelay(true)?;
+    send_frame(&mut stream, hello)?;
+    if recv_frame(&mut stream, 4)? != 1u32.to_le_bytes() {
+        return Err(io::Error::other(
+            "it was started with a different scene or settings",
+        ));
+    }
+    loop {
+        // Wait while other workers still hold tiles that might come back,
+        // keeping this worker from timing out meanwhile
+        let idx = loop {
+            let mut q = queue.lock().unwrap();
+            if let Some(idx) = q.pending.pop_front() {
+                q.in_flight += 1;
+                break Some(idx);
+            }
+            if q.in_flight == 0 {
+                break None;
+            }
+            drop(changed.wait_timeout(q, HEARTBEAT).unwrap());
+            send_frame(&mut stream, &[])?;
+        };
+        let Some(idx) = idx else {
+            let _ = send_frame(&mut stream, DONE);
+            return Ok(());
+        };
+        let result = render_tile(&mut stream, tiles[idx], sizes[idx]);
+        let mut q = queue.lock().unwrap();
+        q.in_flight -= 1;
+        if result.is_err() {
+            q.pending.push_back(idx);
+        }
+        changed.notify_all();
+        drop(q);
+        // The receiver outlives every worker thread, so sending can't fail
+        tx.send((idx, result?)).unwrap();
+    }
+}
+
+fn render_tile(stream: &mut TcpStream, tile: Rect, size: usize) -> io::Result<Vec<u8>> {
+    send_frame(stream, &tile_frame(tile))?;
+    loop {
+        let frame = recv_frame(stream, size)?;
+        if !frame.is_empty() {
+            return Ok(frame);
+        }
+    }
+}
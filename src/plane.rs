@@ -1,27 +1,60 @@
 use crate::hittable::{HitRecord, Hittable};
-use crate::math::{Color, Point3, Vec3};
+use crate::material::Material;
+use crate::math::{orthonormal_basis, Color, Point3, Vec3};
 use crate::ray::Ray;
+use crate::stats::{self, Prim};
 
 pub struct Plane {
     pub point: Point3,
     pub normal: Vec3,
-    pub albedo: Color,
-    pub reflectivity: f64, // NEW
+    pub material: Material,
+    // Texture frame: u runs along `tangent`, v along normal x tangent,
+    // one texture tile every `uv_scale` world units.
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub uv_scale: f64,
 }
 
 impl Plane {
     pub fn new(point: Point3, normal: Vec3, albedo: Color, reflectivity: f64) -> Self {
+        Self::with_material(point, normal, Material::new(albedo, reflectivity))
+    }
+
+    pub fn with_material(point: Point3, normal: Vec3, material: Material) -> Self {
+        let normal = normal.unit();
+        // Default frame: world +X projected onto the plane (or +Z for planes facing X)
+        let hint = if normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
         Self {
             point,
-            normal: normal.unit(),
-            albedo,
-            reflectivity,
+            normal,
+            material,
+            tangent: Vec3::default(),
+            bitangent: Vec3::default(),
+            uv_scale: 1.0,
+        }
+        .with_uv_frame(hint, 1.0)
+    }
+
+    /// Sets the texture u axis (projected onto the plane) and the tile size.
+    pub fn with_uv_frame(mut self, tangent: Vec3, uv_scale: f64) -> Self {
+        let mut t = tangent - self.normal * Vec3::dot(tangent, self.normal);
+        if t.length_squared() < 1e-12 {
+            t = orthonormal_basis(self.normal).0;
         }
+        self.tangent = t.unit();
+        self.bitangent = Vec3::cross(self.normal, self.tangent);
+        self.uv_scale = if uv_scale > 0.0 { uv_scale } else { 1.0 };
+        self
     }
 }
 
 impl Hittable for Plane {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_tests(Prim::Plane, 1);
         let denom = Vec3::dot(r.direction, self.normal);
         if denom.abs() < 1e-6 {
             return None;
@@ -31,13 +64,79 @@ impl Hittable for Plane {
             return None;
         }
         let p = r.at(t);
-        Some(HitRecord::with_face_normal(
-            r,
-            p,
-            self.normal,
-            t,
-            self.albedo,
-            self.reflectivity,
-        ))
+        let d = p - self.point;
+        let u = Vec3::dot(d, self.tangent) / self.uv_scale;
+        let v = Vec3::dot(d, self.bitangent) / self.uv_scale;
+        Some(
+            HitRecord::with_face_normal(r, p, self.normal, t, &self.material).with_uv(
+                u,
+                v,
+                self.tangent * self.uv_scale,
+                self.bitangent * self.uv_scale,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::testing::{check_hit, direction, point, unit};
+    use proptest::prelude::*;
+
+    fn plane(p: Point3, n: Vec3) -> Plane {
+        Plane::new(p, n, Color::new(0.5, 0.5, 0.5), 0.0)
+    }
+
+    proptest! {
+        #[test]
+        fn hits_lie_on_the_plane(
+            p in point(5.0),
+            n in unit(),
+            origin in point(10.0),
+            dir in direction(),
+            t_min in 0.0..1e-3,
+            t_max in 1.0..1e3,
+        ) {
+            let pl = plane(p, n);
+            let r = Ray::new(origin, dir);
+            if let Some(rec) = pl.hit(&r, t_min, t_max) {
+                check_hit(&rec, &r, t_min, t_max)?;
+                let dist = Vec3::dot(rec.p - p, n);
+                prop_assert!(dist.abs() < 1e-9 * (1.0 + rec.p.length()), "{dist} off the plane");
+                prop_assert!((Vec3::dot(rec.normal, n).abs() - 1.0).abs() < 1e-12);
+            }
+        }
+
+        #[test]
+        fn rays_crossing_the_plane_hit_it(
+            p in point(5.0),
+            n in unit(),
+            along in point(5.0),
+            above in 0.01..10.0,
+            below in 0.01..10.0,
+            side in prop::bool::ANY,
+        ) {
+            let pl = plane(p, n);
+            // From one side of the plane to a point on the other side
+            let flat = along - n * Vec3::dot(along, n);
+            let sign = if side { 1.0 } else { -1.0 };
+            let origin = p + flat + n * (above * sign);
+            let dir = p - flat - n * (below * sign) - origin;
+            let r = Ray::new(origin, dir);
+            let rec = pl.hit(&r, 0.0, 1.0);
+            prop_assert!(rec.is_some(), "missed");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 0.0, 1.0)?;
+            prop_assert_eq!(rec.front_face, side);
+        }
+
+        #[test]
+        fn parallel_rays_miss(p in point(5.0), n in unit(), origin in point(10.0), d in unit()) {
+            let pl = plane(p, n);
+            let dir = d - n * Vec3::dot(d, n);
+            prop_assume!(dir.length_squared() > 1e-6);
+            prop_assert!(pl.hit(&Ray::new(origin, dir), 0.0, f64::INFINITY).is_none());
+        }
     }
 }
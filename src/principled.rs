@@ -0,0 +1,267 @@
+use crate::material::{BsdfSample, Bxdf};
+use crate::math::{cosine_sample_hemisphere, luminance, orthonormal_basis, Color, Vec3};
+use std::f64::consts::PI;
+
+/// Disney-style physically based surface parameters, all in [0, 1]. The base
+/// color comes from the material's albedo texture.
+#[derive(Clone, Copy, Debug)]
+pub struct Principled {
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64, // dielectric reflectance, 0.5 = 4% at normal incidence
+    pub clearcoat: f64,
+    pub sheen: f64,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self {
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            clearcoat: 0.0,
+            sheen: 0.0,
+        }
+    }
+}
+
+// The clear coat is a fixed, fairly glossy layer with an IOR of 1.5
+const COAT_ALPHA: f64 = 0.05;
+const COAT_F0: f64 = 0.04;
+
+impl Principled {
+    /// Splits the BSDF for `base` color into its diffuse and glossy parts.
+    /// The diffuse part is meant for direct lighting only; the glossy part is
+    /// also used to continue paths.
+    pub fn lobes(&self, base: Color) -> (PrincipledDiffuse, PrincipledGlossy) {
+        let dielectric_f0 = 0.08 * self.specular.clamp(0.0, 1.0);
+        let metallic = self.metallic.clamp(0.0, 1.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let f0 = white * (dielectric_f0 * (1.0 - metallic)) + base * metallic;
+        let alpha = (self.roughness.clamp(0.0, 1.0).powi(2)).max(1e-3);
+        let clearcoat = self.clearcoat.clamp(0.0, 1.0);
+        (
+            PrincipledDiffuse {
+                base,
+                metallic,
+                sheen: self.sheen.clamp(0.0, 1.0),
+                dielectric_f0,
+                clearcoat,
+            },
+            PrincipledGlossy {
+                f0,
+                alpha,
+                clearcoat,
+            },
+        )
+    }
+}
+
+fn schlick(f0: f64, cos: f64) -> f64 {
+    f0 + (1.0 - f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick_color(f0: Color, cos: f64) -> Color {
+    let w = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * w
+}
+
+// Light the clear coat reflects away before it reaches the layers below
+fn coat_transmission(clearcoat: f64, cos_o: f64) -> f64 {
+    1.0 - clearcoat * schlick(COAT_F0, cos_o)
+}
+
+/// Lambertian base scaled by what the specular layer and clear coat let
+/// through, plus a grazing-angle sheen.
+pub struct PrincipledDiffuse {
+    pub base: Color,
+    pub metallic: f64,
+    pub sheen: f64,
+    pub dielectric_f0: f64,
+    pub clearcoat: f64,
+}
+
+impl PrincipledDiffuse {
+    /// Hemispherical reflectance at normal incidence, used for the ambient term.
+    pub fn albedo(&self) -> Color {
+        self.base * ((1.0 - self.metallic) * (1.0 - self.dielectric_f0))
+    }
+}
+
+impl Bxdf for PrincipledDiffuse {
+    fn eval(&self, wo: Vec3, wi: Vec3, n: Vec3) -> Color {
+        let cos_i = Vec3::dot(wi, n);
+        let cos_o = Vec3::dot(wo, n);
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let kd = (1.0 - self.metallic)
+            * (1.0 - schlick(self.dielectric_f0, cos_o))
+            * coat_transmission(self.clearcoat, cos_o);
+        let mut f = self.base * (kd / PI);
+        if self.sheen > 0.0 {
+            let h = (wo + wi).unit();
+            let cos_d = Vec3::dot(wi, h);
+            let s = self.sheen * (1.0 - self.metallic) * (1.0 - cos_d.clamp(0.0, 1.0)).powi(5);
+            f += Color::new(s, s, s) / PI;
+        }
+        f
+    }
+
+    fn pdf(&self, _wo: Vec3, wi: Vec3, n: Vec3) -> f64 {
+        f64::max(0.0, Vec3::dot(wi, n)) / PI
+    }
+
+    fn sample(&self, wo: Vec3, n: Vec3, u: (f64, f64)) -> Option<BsdfSample> {
+        let wi = cosine_sample_hemisphere(n, u);
+        let pdf = self.pdf(wo, wi, n);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi, n),
+            pdf,
+        })
+    }
+}
+
+/// GGX specular layer plus an optional GGX clear coat on top. Each lobe is
+/// sampled from its distribution of visible normals.
+pub struct PrincipledGlossy {
+    pub f0: Color,
+    pub alpha: f64,
+    pub clearcoat: f64,
+}
+
+// Isotropic GGX (Trowbridge-Reitz) distribution in a local frame where +Z is
+// the shading normal.
+fn ggx_d(alpha: f64, cos_h: f64) -> f64 {
+    if cos_h <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let c2 = cos_h * cos_h;
+    let denom = c2 * (a2 - 1.0) + 1.0;
+    a2 / (PI * denom * denom)
+}
+
+// Smith Lambda for GGX
+fn ggx_lambda(alpha: f64, cos: f64) -> f64 {
+    let c2 = (cos * cos).max(1e-12);
+    let tan2 = (1.0 - c2).max(0.0) / c2;
+    0.5 * (-1.0 + (1.0 + alpha * alpha * tan2).sqrt())
+}
+
+fn ggx_g1(alpha: f64, cos: f64) -> f64 {
+    1.0 / (1.0 + ggx_lambda(alpha, cos))
+}
+
+// Height-correlated masking-shadowing
+fn ggx_g2(alpha: f64, cos_o: f64, cos_i: f64) -> f64 {
+    1.0 / (1.0 + ggx_lambda(alpha, cos_o) + ggx_lambda(alpha, cos_i))
+}
+
+// Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+fn sample_vndf(alpha: f64, wo: Vec3, u: (f64, f64)) -> Vec3 {
+    let vh = Vec3::new(alpha * wo.x, alpha * wo.y, wo.z).unit();
+    let len2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if len2 > 0.0 {
+        Vec3::new(-vh.y, vh.x, 0.0) / len2.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = Vec3::cross(vh, t1);
+    let r = u.0.sqrt();
+    let phi = 2.0 * PI * u.1;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+    let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+    Vec3::new(alpha * nh.x, alpha * nh.y, nh.z.max(1e-6)).unit()
+}
+
+// Density of wi when the half vector is drawn from the visible normals
+fn vndf_pdf(alpha: f64, cos_o: f64, cos_h: f64) -> f64 {
+    ggx_g1(alpha, cos_o) * ggx_d(alpha, cos_h) / (4.0 * cos_o)
+}
+
+impl PrincipledGlossy {
+    // Probability of sampling the clear coat rather than the base specular
+    fn coat_weight(&self, cos_o: f64) -> f64 {
+        let coat = self.clearcoat * schlick(COAT_F0, cos_o);
+        let spec =
+            luminance(schlick_color(self.f0, cos_o)) * coat_transmission(self.clearcoat, cos_o);
+        if coat <= 0.0 {
+            0.0
+        } else {
+            coat / (coat + spec)
+        }
+    }
+}
+
+impl Bxdf for PrincipledGlossy {
+    fn eval(&self, wo: Vec3, wi: Vec3, n: Vec3) -> Color {
+        let cos_i = Vec3::dot(wi, n);
+        let cos_o = Vec3::dot(wo, n);
+        let h = wo + wi;
+        if cos_i <= 0.0 || cos_o <= 0.0 || h.length_squared() == 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let h = h.unit();
+        let cos_h = Vec3::dot(n, h);
+        let cos_d = Vec3::dot(wi, h);
+
+        let spec = schlick_color(self.f0, cos_d)
+            * (ggx_d(self.alpha, cos_h) * ggx_g2(self.alpha, cos_o, cos_i) / (4.0 * cos_o * cos_i));
+        let mut f = spec * coat_transmission(self.clearcoat, cos_o);
+        if self.clearcoat > 0.0 {
+            let coat = self.clearcoat
+                * schlick(COAT_F0, cos_d)
+                * ggx_d(COAT_ALPHA, cos_h)
+                * ggx_g2(COAT_ALPHA, cos_o, cos_i)
+                / (4.0 * cos_o * cos_i);
+            f += Color::new(coat, coat, coat);
+        }
+        f
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, n: Vec3) -> f64 {
+        let cos_i = Vec3::dot(wi, n);
+        let cos_o = Vec3::dot(wo, n);
+        let h = wo + wi;
+        if cos_i <= 0.0 || cos_o <= 0.0 || h.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let cos_h = Vec3::dot(n, h.unit());
+        let w = self.coat_weight(cos_o);
+        (1.0 - w) * vndf_pdf(self.alpha, cos_o, cos_h) + w * vndf_pdf(COAT_ALPHA, cos_o, cos_h)
+    }
+
+    fn sample(&self, wo: Vec3, n: Vec3, u: (f64, f64)) -> Option<BsdfSample> {
+        let cos_o = Vec3::dot(wo, n);
+        if cos_o <= 0.0 {
+            return None;
+        }
+        let w = self.coat_weight(cos_o);
+        let (alpha, u) = if u.0 < w {
+            (COAT_ALPHA, (u.0 / w, u.1))
+        } else {
+            (self.alpha, ((u.0 - w) / (1.0 - w), u.1))
+        };
+        let (t, b) = orthonormal_basis(n);
+        let wo_local = Vec3::new(Vec3::dot(wo, t), Vec3::dot(wo, b), cos_o);
+        let h = sample_vndf(alpha, wo_local, u);
+        let h = t * h.x + b * h.y + n * h.z;
+        let wi = h * (2.0 * Vec3::dot(wo, h)) - wo;
+        let pdf = self.pdf(wo, wi, n);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, wi, n),
+            pdf,
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crate::stats;
+
+const REDRAW: Duration = Duration::from_millis(200);
+const BAR_WIDTH: usize = 30;
+
+/// Progress line on stderr: a bar, the fraction done, elapsed time, ETA and
+/// rays per second. Redrawn in place on a terminal; when stderr is redirected
+/// only the final line is printed.
+pub struct Progress {
+    start: Instant,
+    last_draw: Option<Instant>,
+    time_limit: Option<f64>,
+    live: bool,
+}
+
+impl Progress {
+    pub fn new(time_limit: Option<f64>) -> Self {
+        Self {
+            start: Instant::now(),
+            last_draw: None,
+            time_limit,
+            live: std::io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    /// Whether the `--time-limit` budget is used up.
+    pub fn out_of_time(&self) -> bool {
+        self.time_limit.is_some_and(|t| self.elapsed() >= t)
+    }
+
+    /// `done` is the fraction of the work finished, in [0, 1].
+    pub fn update(&mut self, done: f64) {
+        if !self.live || self.last_draw.is_some_and(|t| t.elapsed() < REDRAW) {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+        eprint!("\r{}", self.line(done));
+        let _ = std::io::stderr().flush();
+    }
+
+    pub fn finish(&mut self, done: f64) {
+        let end = if self.live { "\r" } else { "" };
+        eprintln!("{end}{}", self.line(done));
+    }
+
+    fn line(&self, done: f64) -> String {
+        let elapsed = self.elapsed();
+        let mut done = done.clamp(0.0, 1.0);
+        let mut eta = if done > 0.0 {
+            elapsed * (1.0 - done) / done
+        } else {
+            f64::INFINITY
+        };
+        // A time limit caps the ETA, and the bar shows whichever ends first
+        if let Some(limit) = self.time_limit {
+            eta = eta.min((limit - elapsed).max(0.0));
+            done = done.max((elapsed / limit).min(1.0));
+        }
+        let filled = (done * BAR_WIDTH as f64).round() as usize;
+        let rays_per_sec = stats::rays() as f64 / elapsed.max(1e-3);
+        format!(
+            "[{}{}] {:5.1}%  {}  ETA {}  {:.2} Mrays/s ",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            done * 100.0,
+            format_secs(elapsed),
+            format_secs(eta),
+            rays_per_sec / 1e6
+        )
+    }
+}
+
+fn format_secs(s: f64) -> String {
+    if !s.is_finite() {
+        return "--:--".into();
+    }
+    let s = s.round() as u64;
+    if s >= 3600 {
+        format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+    } else {
+        format!("{:02}:{:02}", s / 60, s % 60)
+    }
+}
@@ -0,0 +1,387 @@
+/// Source of the random numbers for one pixel sample. Every value is a pure
+/// function of (pixel, sample index, dimension, seed), so renders are
+/// reproducible and independent of the order pixels are visited in.
+///
+/// The first 2D value of each sample is the position inside the pixel; the
+/// following ones are consumed by shading in a fixed order.
+pub trait Sampler {
+    /// Starts sample `index` of pixel `(x, y)`; dimensions restart at zero.
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+    fn next_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SamplerKind {
+    #[default]
+    Random,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "random" => Some(SamplerKind::Random),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            _ => None,
+        }
+    }
+
+    /// `spp` is the number of samples per pixel the sampler should be
+    /// optimized for (only the stratified sampler depends on it).
+    pub fn create(self, spp: u32, seed: u64) -> Box<dyn Sampler> {
+        let state = SampleState {
+            seed,
+            ..SampleState::default()
+        };
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler { state }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {
+                state,
+                spp: spp.max(1),
+            }),
+            SamplerKind::Halton => Box::new(HaltonSampler { state }),
+            SamplerKind::Sobol => Box::new(SobolSampler { state }),
+        }
+    }
+}
+
+// 64-bit finalizer from MurmurHash3
+fn mix64(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    x ^= x >> 33;
+    x
+}
+
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, &v| {
+        mix64(h ^ v.wrapping_add(0x632b_e59b_d9b4_e019))
+    })
+}
+
+fn to_unit(bits: u64) -> f64 {
+    // Top 53 bits -> [0, 1)
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+fn u32_to_unit(x: u32) -> f64 {
+    (x as f64 * (1.0 / 4_294_967_296.0)).min(1.0 - f64::EPSILON)
+}
+
+#[derive(Default)]
+struct SampleState {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dim: u32,
+}
+
+impl SampleState {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x as u64) << 32 | y as u64;
+        self.index = index;
+        self.dim = 0;
+    }
+
+    // Returns the current dimension and advances by `n`
+    fn take(&mut self, n: u32) -> u32 {
+        let d = self.dim;
+        self.dim += n;
+        d
+    }
+
+    // Hash shared by every sample of this pixel in dimension `dim`
+    fn pixel_hash(&self, dim: u32) -> u64 {
+        hash(&[self.seed, self.pixel, dim as u64])
+    }
+
+    fn random(&self, dim: u32) -> f64 {
+        to_unit(hash(&[
+            self.seed,
+            self.pixel,
+            self.index as u64,
+            dim as u64,
+        ]))
+    }
+}
+
+/// Independent uniform random numbers.
+pub struct RandomSampler {
+    state: SampleState,
+}
+
+impl Sampler for RandomSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let d = self.state.take(2);
+        (self.state.random(d), self.state.random(d + 1))
+    }
+}
+
+// Kensler 2013, "Correlated Multi-Jittered Sampling": a hashed permutation of
+// [0, len) selected by `p`.
+fn permute(mut i: u32, len: u32, p: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % len
+}
+
+/// Jittered strata: a square grid per 2D dimension, visited in a different random order per pixel. Samples
+/// beyond `spp` fall back to independent random numbers.
+pub struct StratifiedSampler {
+    state: SampleState,
+    spp: u32,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let d = self.state.take(2);
+        // Smallest square grid with at least spp cells
+        let side = (self.spp as f64).sqrt().ceil() as u32;
+        let cells = side * side;
+        if self.state.index >= self.spp {
+            return (self.state.random(d), self.state.random(d + 1));
+        }
+        let cell = permute(self.state.index, cells, self.state.pixel_hash(d) as u32);
+        (
+            ((cell % side) as f64 + self.state.random(d)) / side as f64,
+            ((cell / side) as f64 + self.state.random(d + 1)) / side as f64,
+        )
+    }
+}
+
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+// Radical inverse of `i` in `base` with Owen scrambling: each digit is
+// permuted by a hash of the digits before it. Trailing zero digits are
+// scrambled too, until they no longer matter in double precision.
+fn owen_radical_inverse(base: u32, mut i: u32, seed: u64) -> f64 {
+    let inv = 1.0 / base as f64;
+    let mut f = inv;
+    let mut r = 0.0;
+    let mut prefix = seed;
+    while f > 1e-15 {
+        let digit = i % base;
+        r += permute(digit, base, prefix as u32) as f64 * f;
+        prefix = mix64(prefix ^ (digit as u64 + 1));
+        i /= base;
+        f *= inv;
+    }
+    r.min(1.0 - f64::EPSILON)
+}
+
+/// Halton sequence (one prime base per dimension) with per-pixel Owen
+/// scrambling, which also breaks the correlation between the large bases.
+/// Dimensions past the prime table fall back to independent random numbers.
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    fn sample(&self, dim: u32) -> f64 {
+        match PRIMES.get(dim as usize) {
+            Some(&base) => owen_radical_inverse(base, self.state.index, self.state.pixel_hash(dim)),
+            None => self.state.random(dim),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let d = self.state.take(2);
+        (self.sample(d), self.sample(d + 1))
+    }
+}
+
+// First two Sobol dimensions as 32-bit fixed point
+fn sobol_dim0(i: u32) -> u32 {
+    i.reverse_bits()
+}
+
+fn sobol_dim1(mut i: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut r = 0;
+    while i != 0 {
+        if i & 1 != 0 {
+            r ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    r
+}
+
+// Laine-Karras style hash that only propagates bits upwards
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+// Owen (nested uniform) scrambling of a base-2 fraction
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Owen-scrambled Sobol points (Burley 2020, "Practical Hash-based Owen
+/// Scrambling"). Each 2D dimension pair uses the first two Sobol dimensions
+/// with its own scrambled sample order, so pairs stay well stratified and
+/// uncorrelated with each other.
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    fn sample_2d(&self, dim: u32) -> (f64, f64) {
+        let h = self.state.pixel_hash(dim);
+        let index = owen_scramble(self.state.index, h as u32);
+        let (sx, sy) = (mix64(h) as u32, (mix64(h) >> 32) as u32);
+        (
+            u32_to_unit(owen_scramble(sobol_dim0(index), sx)),
+            u32_to_unit(owen_scramble(sobol_dim1(index), sy)),
+        )
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn next_2d(&mut self) -> (f64, f64) {
+        let d = self.state.take(2);
+        self.sample_2d(d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first `n` samples of pixel (3, 5) in 2D dimension pair `pair`
+    fn points(kind: SamplerKind, spp: u32, n: u32, pair: usize) -> Vec<(f64, f64)> {
+        let mut sampler = kind.create(spp, 7);
+        (0..n)
+            .map(|i| {
+                sampler.start_sample(3, 5, i);
+                for _ in 0..pair {
+                    sampler.next_2d();
+                }
+                sampler.next_2d()
+            })
+            .collect()
+    }
+
+    // Whether each cell of an nx by ny grid holds exactly one point
+    fn one_per_cell(points: &[(f64, f64)], nx: usize, ny: usize) -> bool {
+        let mut cells = vec![0; nx * ny];
+        for &(x, y) in points {
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            cells[(y * ny as f64) as usize * nx + (x * nx as f64) as usize] += 1;
+        }
+        cells.iter().all(|&n| n == 1)
+    }
+
+    #[test]
+    fn stratified_samples_fill_every_stratum() {
+        for pair in [0, 3] {
+            assert!(one_per_cell(
+                &points(SamplerKind::Stratified, 16, 16, pair),
+                4,
+                4
+            ));
+        }
+        // Counts that aren't squares round the grid up and leave cells empty
+        let p = points(SamplerKind::Stratified, 12, 12, 0);
+        assert!(!one_per_cell(&p, 4, 4) && p.len() == 12);
+    }
+
+    #[test]
+    fn sobol_and_halton_points_are_low_discrepancy() {
+        for pair in [0, 2] {
+            // Every elementary interval of area 1/64 holds one of 64 Sobol points
+            let p = points(SamplerKind::Sobol, 64, 64, pair);
+            for (nx, ny) in [(64, 1), (32, 2), (16, 4), (8, 8), (4, 16), (2, 32), (1, 64)] {
+                assert!(one_per_cell(&p, nx, ny), "pair {pair}, {nx}x{ny}");
+            }
+        }
+        // Halton in bases 2 and 3: 4 * 9 points fill a 4x9 grid, the first 8
+        // fill 8 columns and the first 9 fill 9 rows
+        let p = points(SamplerKind::Halton, 36, 36, 0);
+        assert!(one_per_cell(&p, 4, 9));
+        assert!(one_per_cell(&p[..8], 8, 1) && one_per_cell(&p[..9], 1, 9));
+        // Independent random points almost never do
+        assert!(!one_per_cell(&points(SamplerKind::Random, 64, 64, 0), 8, 8));
+    }
+
+    #[test]
+    fn samples_depend_only_on_pixel_index_and_seed() {
+        for kind in [
+            SamplerKind::Random,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let forward = points(kind, 16, 16, 1);
+            let mut sampler = kind.create(16, 7);
+            for i in (0..16).rev() {
+                sampler.start_sample(3, 5, i);
+                sampler.next_2d();
+                assert_eq!(sampler.next_2d(), forward[i as usize], "{kind:?}");
+            }
+            assert_ne!(kind.create(16, 8).next_2d(), forward[0], "{kind:?}");
+        }
+    }
+}
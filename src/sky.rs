@@ -0,0 +1,197 @@
+use crate::envmap::EnvMap;
+use crate::light::SunLight;
+use crate::math::{Color, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// What a ray sees when it escapes the scene.
+pub enum Background {
+    /// The procedural white-to-blue gradient (not a light source).
+    Gradient,
+    /// An environment map; also registered in the scene's light list.
+    Env(Arc<EnvMap>),
+    /// Baked Preetham sky with a sun; both are also lights.
+    Physical(PhysicalSky),
+}
+
+impl Background {
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        match self {
+            Background::Gradient => {
+                let unit_dir = dir.unit();
+                let t = 0.5 * (unit_dir.y + 1.0);
+                (Color::new(1.0, 1.0, 1.0) * (1.0 - t)) + (Color::new(0.5, 0.7, 1.0) * t)
+            }
+            Background::Env(env) => env.radiance(dir),
+            Background::Physical(sky) => sky.radiance(dir),
+        }
+    }
+}
+
+// Preetham et al. 1999, "A Practical Analytic Model for Daylight".
+// Luminance is in kcd/m^2; SKY_SCALE brings it to roughly the brightness
+// range of the default point light.
+const SKY_SCALE: f64 = 0.03;
+
+/// Analytic clear-sky model for a given sun direction and turbidity.
+pub struct PreethamSky {
+    pub sun_dir: Vec3, // unit vector towards the sun
+    pub turbidity: f64,
+    zenith: (f64, f64, f64), // (Y, x, y) at the zenith
+    perez_y: [f64; 5],
+    perez_cx: [f64; 5],
+    perez_cy: [f64; 5],
+    theta_s: f64,
+}
+
+impl PreethamSky {
+    pub fn new(sun_dir: Vec3, turbidity: f64) -> Self {
+        let sun_dir = sun_dir.unit();
+        let t = turbidity.clamp(1.7, 10.0);
+        // The fit is only valid above the horizon
+        let theta_s = sun_dir.y.clamp(-1.0, 1.0).acos().min(PI / 2.0 - 1e-3);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let yz = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let xz = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let cyz = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        Self {
+            sun_dir,
+            turbidity: t,
+            zenith: (yz, xz, cyz),
+            perez_y: [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            perez_cx: [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            perez_cy: [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+            theta_s,
+        }
+    }
+
+    /// Sky radiance (linear sRGB) along `dir`, excluding the sun disc.
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        let d = dir.unit();
+        // Below the horizon: a darkened copy of the horizon as a stand-in for the ground
+        let (cos_theta, ground) = if d.y < 0.01 { (0.01, 0.25) } else { (d.y, 1.0) };
+        let horizon_d = Vec3::new(d.x, cos_theta, d.z).unit();
+        let gamma = Vec3::dot(horizon_d, self.sun_dir).clamp(-1.0, 1.0).acos();
+        let theta = cos_theta.acos();
+
+        let ratio = |c: &[f64; 5]| perez(c, theta, gamma) / perez(c, 0.0, self.theta_s);
+        let y = self.zenith.0 * ratio(&self.perez_y);
+        let x = self.zenith.1 * ratio(&self.perez_cx);
+        let cy = self.zenith.2 * ratio(&self.perez_cy);
+        xyy_to_rgb(x, cy, y * SKY_SCALE) * ground
+    }
+
+    /// Bakes the sky into an equirectangular map so it can be importance-sampled as a light.
+    pub fn to_envmap(&self, width: usize, height: usize, intensity: f64) -> EnvMap {
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let theta = PI * (y as f64 + 0.5) / height as f64;
+            for x in 0..width {
+                let phi = 2.0 * PI * (x as f64 + 0.5) / width as f64;
+                let s = theta.sin();
+                // Same parameterization as EnvMap with zero rotation
+                let d = Vec3::new(s * phi.sin(), theta.cos(), -s * phi.cos());
+                texels.push(self.radiance(d));
+            }
+        }
+        EnvMap::new(width, height, texels, 0.0, intensity)
+    }
+
+    /// Fraction of sunlight that reaches the ground (Rayleigh + aerosol
+    /// extinction from the paper's appendix, evaluated at R/G/B wavelengths).
+    pub fn sun_transmittance(&self) -> Color {
+        let theta_deg = self.theta_s.to_degrees();
+        let mass = 1.0 / (self.theta_s.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let tau = |lambda_um: f64| {
+            let rayleigh = (-0.008735 * lambda_um.powf(-4.08) * mass).exp();
+            let aerosol = (-beta * lambda_um.powf(-1.3) * mass).exp();
+            rayleigh * aerosol
+        };
+        Color::new(tau(0.65), tau(0.57), tau(0.475))
+    }
+}
+
+fn perez(c: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+    let cos_g = gamma.cos();
+    (1.0 + c[0] * (c[1] / theta.cos().max(0.01)).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_g * cos_g)
+}
+
+fn xyy_to_rgb(x: f64, y: f64, lum: f64) -> Color {
+    if y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let cx = x * lum / y;
+    let cz = (1.0 - x - y) * lum / y;
+    Color::new(
+        (3.2406 * cx - 1.5372 * lum - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * lum + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * lum + 1.0570 * cz).max(0.0),
+    )
+}
+
+/// Unit vector towards a sun at the given elevation and compass azimuth
+/// (degrees, clockwise from north). North is -Z and east is +X.
+pub fn sun_direction(elevation_deg: f64, azimuth_deg: f64) -> Vec3 {
+    let (el, az) = (elevation_deg.to_radians(), azimuth_deg.to_radians());
+    Vec3::new(az.sin() * el.cos(), el.sin(), -az.cos() * el.cos())
+}
+
+/// Approximate solar (elevation, azimuth) in degrees for a calendar date,
+/// local solar time in hours and latitude in degrees. Ignores the equation
+/// of time, so 12:00 is always solar noon.
+pub fn solar_position(month: u32, day: u32, hours: f64, latitude_deg: f64) -> (f64, f64) {
+    const CUM_DAYS: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    let n = CUM_DAYS[(month.clamp(1, 12) - 1) as usize] + day.clamp(1, 31);
+    let decl = (-23.44_f64).to_radians() * (2.0 * PI / 365.0 * (n as f64 + 10.0)).cos();
+    let hour_angle = (15.0 * (hours - 12.0)).to_radians();
+    let lat = latitude_deg.to_radians();
+
+    let sin_el = lat.sin() * decl.sin() + lat.cos() * decl.cos() * hour_angle.cos();
+    let el = sin_el.clamp(-1.0, 1.0).asin();
+    let cos_az = (decl.sin() - el.sin() * lat.sin()) / (el.cos() * lat.cos()).max(1e-9);
+    let mut az = cos_az.clamp(-1.0, 1.0).acos().to_degrees();
+    if hour_angle > 0.0 {
+        az = 360.0 - az; // afternoon: sun is west of the meridian
+    }
+    (el.to_degrees(), az)
+}
+
+/// Baked sky plus the visible sun disc, used as the background. The sky is the
+/// same map the light list samples, so MIS sees one radiance function.
+pub struct PhysicalSky {
+    pub sky: Arc<EnvMap>,
+    pub sun: Arc<SunLight>,
+}
+
+impl PhysicalSky {
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        self.sky.radiance(dir) + self.sun.disc_radiance(dir)
+    }
+}
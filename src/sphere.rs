@@ -1,27 +1,52 @@
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, Shape};
+use crate::material::Material;
 use crate::math::{Color, Point3, Vec3};
 use crate::ray::Ray;
+use crate::stats::{self, Prim};
+use std::f64::consts::PI;
 
 pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
-    pub albedo: Color,
-    pub reflectivity: f64, // NEW
+    pub material: Material,
 }
 
 impl Sphere {
     pub fn new(center: Point3, radius: f64, albedo: Color, reflectivity: f64) -> Self {
+        Self::with_material(center, radius, Material::new(albedo, reflectivity))
+    }
+
+    pub fn with_material(center: Point3, radius: f64, material: Material) -> Self {
         Self {
             center,
             radius,
-            albedo,
-            reflectivity,
+            material,
         }
     }
+
+    // Spherical coordinates: u = azimuth around +Y starting at -X, v = polar angle from -Y
+    fn surface_uv(&self, p: Point3) -> (f64, f64, Vec3, Vec3) {
+        let d = p - self.center;
+        let n = d / self.radius;
+        let theta = (-n.y).clamp(-1.0, 1.0).acos();
+        let phi = (-n.z).atan2(n.x) + PI;
+        let u = phi / (2.0 * PI);
+        let v = theta / PI;
+
+        let dpdu = Vec3::new(d.z, 0.0, -d.x) * (2.0 * PI);
+        let sin_theta = theta.sin().max(1e-9);
+        let dpdv = Vec3::new(
+            -d.x * d.y / (self.radius * sin_theta),
+            self.radius * sin_theta,
+            -d.z * d.y / (self.radius * sin_theta),
+        ) * PI;
+        (u, v, dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_tests(Prim::Sphere, 1);
         let oc = r.origin - self.center;
         let a = Vec3::dot(r.direction, r.direction);
         let half_b = Vec3::dot(oc, r.direction);
@@ -43,13 +68,102 @@ impl Hittable for Sphere {
 
         let p = r.at(root);
         let outward_normal = (p - self.center) / self.radius;
-        Some(HitRecord::with_face_normal(
-            r,
-            p,
-            outward_normal,
-            root,
-            self.albedo,
-            self.reflectivity,
-        ))
+        let (u, v, dpdu, dpdv) = self.surface_uv(p);
+        Some(
+            HitRecord::with_face_normal(r, p, outward_normal, root, &self.material)
+                .with_uv(u, v, dpdu, dpdv),
+        )
+    }
+}
+
+impl Shape for Sphere {
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample_surface(&self, u: (f64, f64)) -> (Point3, Vec3) {
+        let z = 1.0 - 2.0 * u.0;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let n = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+        (self.center + n * self.radius, n)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::testing::{check_hit, direction, in_ball, point, unit};
+    use proptest::prelude::*;
+
+    fn sphere(center: Point3, radius: f64) -> Sphere {
+        Sphere::new(center, radius, Color::new(0.5, 0.5, 0.5), 0.0)
+    }
+
+    fn on_surface(s: &Sphere, p: Point3) -> bool {
+        ((p - s.center).length() - s.radius).abs() < 1e-9 * (1.0 + s.radius)
+    }
+
+    proptest! {
+        #[test]
+        fn hits_lie_on_the_surface(
+            center in point(2.0),
+            radius in 0.1..3.0,
+            origin in point(10.0),
+            dir in direction(),
+            t_min in 0.0..1e-3,
+            t_max in 1.0..1e3,
+        ) {
+            let s = sphere(center, radius);
+            let r = Ray::new(origin, dir);
+            if let Some(rec) = s.hit(&r, t_min, t_max) {
+                check_hit(&rec, &r, t_min, t_max)?;
+                prop_assert!(on_surface(&s, rec.p), "{:?} is off the surface", rec.p);
+            }
+        }
+
+        #[test]
+        fn rays_aimed_at_the_sphere_hit_its_outside(
+            center in point(2.0),
+            radius in 0.1..3.0,
+            from in unit(),
+            distance in 1.01..20.0,
+            target in in_ball(),
+            len in 0.01..100.0,
+        ) {
+            let s = sphere(center, radius);
+            let origin = center + from * (radius * distance);
+            let dir = (center + target * (radius * 0.99) - origin).unit() * len;
+            let r = Ray::new(origin, dir);
+            let rec = s.hit(&r, 1e-9, f64::INFINITY);
+            prop_assert!(rec.is_some(), "missed");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 1e-9, f64::INFINITY)?;
+            prop_assert!(rec.front_face);
+            prop_assert!(on_surface(&s, rec.p));
+        }
+
+        #[test]
+        fn rays_from_inside_hit_exactly_once(
+            center in point(2.0),
+            radius in 0.1..3.0,
+            start in in_ball(),
+            dir in direction(),
+        ) {
+            let s = sphere(center, radius);
+            let r = Ray::new(center + start * (radius * 0.99), dir);
+            let rec = s.hit(&r, 0.0, f64::INFINITY);
+            prop_assert!(rec.is_some(), "no exit hit");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 0.0, f64::INFINITY)?;
+            prop_assert!(!rec.front_face);
+            prop_assert!(on_surface(&s, rec.p));
+            let beyond = Ray::new(rec.p, dir.unit());
+            prop_assert!(s.hit(&beyond, 1e-6, f64::INFINITY).is_none(), "hit twice");
+        }
     }
 }
@@ -0,0 +1,266 @@
+use crate::checkpoint::{put_u64, Reader};
+use std::cell::Cell;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+#[derive(Clone, Copy)]
+pub enum RayKind {
+    /// From the camera
+    Primary,
+    /// Toward lights, from light or BSDF sampling in direct lighting
+    Shadow,
+    /// Path continuation: mirror reflection or a glossy bounce
+    Reflection,
+    /// Extra camera ray that collects denoiser features
+    Guide,
+}
+
+#[derive(Clone, Copy)]
+pub enum Prim {
+    Sphere,
+    Plane,
+    Cube,
+    Cylinder,
+    Triangle,
+}
+
+const RAY_NAMES: [&str; 4] = ["primary", "shadow", "reflection", "guide"];
+const PRIM_NAMES: [&str; 5] = ["sphere", "plane", "cube", "cylinder", "triangle"];
+
+// Rendering runs on one thread, so its counters are the totals. Plain cells
+// keep the per-ray bookkeeping down to a few unlocked adds.
+thread_local! {
+    static RAYS: [Cell<u64>; 4] = const { [const { Cell::new(0) }; 4] };
+    static TESTS: [Cell<u64>; 5] = const { [const { Cell::new(0) }; 5] };
+    static INTERSECT_CALLS: Cell<u64> = const { Cell::new(0) };
+    static INTERSECT_NANOS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn bump(c: &Cell<u64>, n: u64) {
+    c.set(c.get() + n);
+}
+
+/// Counts one ray cast into the scene.
+pub fn count_ray(kind: RayKind) {
+    RAYS.with(|r| bump(&r[kind as usize], 1));
+}
+
+/// Counts `n` ray-primitive intersection tests.
+pub fn count_tests(prim: Prim, n: u64) {
+    TESTS.with(|t| bump(&t[prim as usize], n));
+}
+
+// Reading the clock costs about as much as intersecting a small scene, so
+// only every TIME_EVERY-th call is timed (and counted TIME_EVERY times), with
+// the cost of the clock reads themselves taken out
+const TIME_EVERY: u64 = 16;
+
+fn clock_overhead() -> u64 {
+    static NANOS: OnceLock<u64> = OnceLock::new();
+    *NANOS.get_or_init(|| {
+        const N: u32 = 1000;
+        let start = Instant::now();
+        for _ in 0..N {
+            std::hint::black_box(Instant::now().elapsed());
+        }
+        (start.elapsed() / N).as_nanos() as u64
+    })
+}
+
+/// Runs `f`, adding an estimate of the time it takes to the intersection
+/// total.
+pub fn time_intersect<T>(f: impl FnOnce() -> T) -> T {
+    let calls = INTERSECT_CALLS.with(|c| {
+        bump(c, 1);
+        c.get()
+    });
+    if !calls.is_multiple_of(TIME_EVERY) {
+        return f();
+    }
+    let overhead = clock_overhead();
+    let start = Instant::now();
+    let out = f();
+    let nanos = (start.elapsed().as_nanos() as u64).saturating_sub(overhead);
+    INTERSECT_NANOS.with(|n| bump(n, nanos * TIME_EVERY));
+    out
+}
+
+/// Total rays of every kind so far.
+pub fn rays() -> u64 {
+    RAYS.with(|r| r.iter().map(Cell::get).sum())
+}
+
+/// Peak resident memory (VmHWM) in bytes; only known on Linux.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Values of all counters, so a worker can report its share of the work to
+/// the coordinator.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counters {
+    rays: [u64; 4],
+    tests: [u64; 5],
+    intersect_nanos: u64,
+}
+
+impl Counters {
+    pub fn current() -> Self {
+        Self {
+            rays: RAYS.with(|r| r.each_ref().map(Cell::get)),
+            tests: TESTS.with(|t| t.each_ref().map(Cell::get)),
+            intersect_nanos: INTERSECT_NANOS.with(Cell::get),
+        }
+    }
+
+    /// What was counted after `earlier` was taken.
+    pub fn since(&self, earlier: &Counters) -> Self {
+        Self {
+            rays: std::array::from_fn(|k| self.rays[k] - earlier.rays[k]),
+            tests: std::array::from_fn(|k| self.tests[k] - earlier.tests[k]),
+            intersect_nanos: self.intersect_nanos - earlier.intersect_nanos,
+        }
+    }
+
+    /// Adds counts gathered elsewhere to this thread's counters.
+    pub fn add_to_totals(&self) {
+        RAYS.with(|r| r.iter().zip(self.rays).for_each(|(c, n)| bump(c, n)));
+        TESTS.with(|t| t.iter().zip(self.tests).for_each(|(c, n)| bump(c, n)));
+        INTERSECT_NANOS.with(|c| bump(c, self.intersect_nanos));
+    }
+
+    pub const SAVED_BYTES: usize = 80;
+
+    pub fn save(&self, out: &mut Vec<u8>) {
+        for v in self.rays.iter().chain(&self.tests) {
+            put_u64(out, *v);
+        }
+        put_u64(out, self.intersect_nanos);
+    }
+
+    pub fn load(r: &mut Reader) -> Result<Self, String> {
+        let mut c = Self::default();
+        for v in c.rays.iter_mut().chain(&mut c.tests) {
+            *v = r.u64()?;
+        }
+        c.intersect_nanos = r.u64()?;
+        Ok(c)
+    }
+}
+
+/// Snapshot of the counters at the end of a render.
+pub struct Report {
+    width: usize,
+    height: usize,
+    render_secs: f64,
+    intersect_secs: f64,
+    rays: [u64; 4],
+    tests: [u64; 5],
+    peak_memory: Option<u64>,
+}
+
+impl Report {
+    /// `render_secs` is the time spent rendering: wall-clock time locally,
+    /// the workers' combined busy time for a distributed render.
+    pub fn collect(width: usize, height: usize, render_secs: f64) -> Self {
+        let c = Counters::current();
+        Self {
+            width,
+            height,
+            render_secs,
+            intersect_secs: c.intersect_nanos as f64 * 1e-9,
+            rays: c.rays,
+            tests: c.tests,
+            peak_memory: peak_memory(),
+        }
+    }
+
+    // One camera ray per sample taken in this run (a resumed render's earlier
+    // samples aren't counted)
+    fn samples(&self) -> u64 {
+        self.rays[RayKind::Primary as usize]
+    }
+
+    fn shading_secs(&self) -> f64 {
+        (self.render_secs - self.intersect_secs).max(0.0)
+    }
+
+    // Ray segments per camera path: the primary ray plus its continuations
+    fn mean_path_depth(&self) -> f64 {
+        let primary = self.rays[RayKind::Primary as usize];
+        if primary == 0 {
+            return 0.0;
+        }
+        (primary + self.rays[RayKind::Reflection as usize]) as f64 / primary as f64
+    }
+
+    pub fn print(&self) {
+        let pct = |x: f64| 100.0 * x / self.render_secs.max(1e-9);
+        let list = |names: &[&str], counts: &[u64]| {
+            names
+                .iter()
+                .zip(counts)
+                .map(|(n, c)| format!("{c} {n}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        eprintln!("render stats:");
+        // The split is extrapolated from timing a fraction of the hit tests
+        eprintln!(
+            "  time         {:.2} s: est. {:.2} s intersecting ({:.0}%), {:.2} s shading ({:.0}%)",
+            self.render_secs,
+            self.intersect_secs,
+            pct(self.intersect_secs),
+            self.shading_secs(),
+            pct(self.shading_secs())
+        );
+        eprintln!(
+            "  samples      {} ({:.1} per pixel)",
+            self.samples(),
+            self.samples() as f64 / (self.width * self.height).max(1) as f64
+        );
+        eprintln!("  rays         {}", list(&RAY_NAMES, &self.rays));
+        eprintln!(
+            "  path depth   {:.2} segments per camera ray",
+            self.mean_path_depth()
+        );
+        eprintln!("  tests        {}", list(&PRIM_NAMES, &self.tests));
+        match self.peak_memory {
+            Some(b) => eprintln!("  peak memory  {:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+            None => eprintln!("  peak memory  unknown"),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let object = |names: &[&str], counts: &[u64]| {
+            let fields: Vec<String> = names
+                .iter()
+                .zip(counts)
+                .map(|(n, c)| format!("\"{n}\": {c}"))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        };
+        let peak = self
+            .peak_memory
+            .map_or("null".to_string(), |b| b.to_string());
+        format!(
+            "{{\n  \"width\": {},\n  \"height\": {},\n  \"samples\": {},\n  \
+             \"render_seconds\": {:.6},\n  \"intersect_seconds\": {:.6},\n  \
+             \"shading_seconds\": {:.6},\n  \"rays\": {},\n  \"mean_path_depth\": {:.6},\n  \
+             \"intersection_tests\": {},\n  \"peak_memory_bytes\": {}\n}}\n",
+            self.width,
+            self.height,
+            self.samples(),
+            self.render_secs,
+            self.intersect_secs,
+            self.shading_secs(),
+            object(&RAY_NAMES, &self.rays),
+            self.mean_path_depth(),
+            object(&PRIM_NAMES, &self.tests),
+            peak
+        )
+    }
+}
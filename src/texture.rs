@@ -0,0 +1,280 @@
+use crate::image_io::{self, Image};
+use crate::math::{Color, Point3};
+use std::sync::Arc;
+
+/// Color lookup at a surface point. `(u, v)` is the surface parameterization
+/// and `p` the hit point in world space.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.color
+    }
+}
+
+/// Solid (3D) checkerboard of cubes with edge `size`, alternating two textures.
+pub struct Checker {
+    pub size: f64,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn new(size: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { size, even, odd }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let inv = 1.0 / self.size;
+        // Tiny offset keeps a plane lying exactly on a cell boundary from flickering
+        let cell = |c: f64| ((c + 1e-6) * inv).floor() as i64;
+        if (cell(p.x) + cell(p.y) + cell(p.z)).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "repeat" => Some(WrapMode::Repeat),
+            "clamp" => Some(WrapMode::Clamp),
+            "mirror" => Some(WrapMode::Mirror),
+            _ => None,
+        }
+    }
+
+    fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Bilinearly filtered image lookup. `v = 0` is the bottom row of the image.
+pub struct ImageTexture {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<Color>, // linear RGB, row 0 at the top
+    pub wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn from_image(img: &Image, wrap: WrapMode) -> Self {
+        let texels = img
+            .pixels
+            .iter()
+            .map(|c| {
+                Color::new(
+                    image_io::srgb_to_linear(c.x),
+                    image_io::srgb_to_linear(c.y),
+                    image_io::srgb_to_linear(c.z),
+                )
+            })
+            .collect();
+        Self {
+            width: img.width,
+            height: img.height,
+            texels,
+            wrap,
+        }
+    }
+
+    pub fn load(path: &str, wrap: WrapMode) -> Result<Self, String> {
+        Ok(Self::from_image(&image_io::load_image(path)?, wrap))
+    }
+
+    /// Loads an image holding data rather than color (normal or height maps),
+    /// so texel values are used as stored, without sRGB decoding.
+    pub fn load_data(path: &str, wrap: WrapMode) -> Result<Self, String> {
+        let img = image_io::load_image(path)?;
+        Ok(Self {
+            width: img.width,
+            height: img.height,
+            texels: img.pixels,
+            wrap,
+        })
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.apply(x, self.width);
+        let y = self.wrap.apply(y, self.height);
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        // Texel centers sit at half-integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/// Improved Perlin gradient noise with a fixed permutation, so renders are reproducible.
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut p: Vec<u8> = (0..=255).collect();
+        // xorshift64* driven Fisher-Yates shuffle
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        for i in (1..256).rev() {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            let r = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+            p.swap(i, (r % (i as u64 + 1)) as usize);
+        }
+        let mut perm = [0u8; 512];
+        for i in 0..512 {
+            perm[i] = p[i & 255];
+        }
+        Self { perm }
+    }
+
+    /// Noise in roughly [-1, 1].
+    pub fn noise(&self, p: Point3) -> f64 {
+        let (xf, yf, zf) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (x, y, z) = (p.x - xf, p.y - yf, p.z - zf);
+        let (xi, yi, zi) = (
+            (xf as i64 & 255) as usize,
+            (yf as i64 & 255) as usize,
+            (zf as i64 & 255) as usize,
+        );
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let pm = &self.perm;
+        let a = pm[xi] as usize + yi;
+        let (aa, ab) = (pm[a] as usize + zi, pm[a + 1] as usize + zi);
+        let b = pm[xi + 1] as usize + yi;
+        let (ba, bb) = (pm[b] as usize + zi, pm[b + 1] as usize + zi);
+        let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(pm[aa], x, y, z), grad(pm[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(pm[ab], x, y - 1.0, z),
+                    grad(pm[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(pm[aa + 1], x, y, z - 1.0),
+                    grad(pm[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(pm[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(pm[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Fractal Brownian motion: `octaves` layers of noise, each at double
+    /// the frequency and half the amplitude of the previous one.
+    pub fn fbm(&self, p: Point3, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut amp = 1.0;
+        let mut norm = 0.0;
+        let mut q = p;
+        for _ in 0..octaves.max(1) {
+            sum += amp * self.noise(q);
+            norm += amp;
+            amp *= 0.5;
+            q *= 2.0;
+        }
+        sum / norm
+    }
+}
+
+// Dot product with one of 12 edge-midpoint gradients picked by the hash
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Blends two colors by Perlin noise (one octave) or fBm (several octaves).
+pub struct NoiseTexture {
+    pub noise: Perlin,
+    pub scale: f64, // spatial frequency
+    pub octaves: u32,
+    pub low: Color,
+    pub high: Color,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, octaves: u32, low: Color, high: Color) -> Self {
+        Self {
+            noise: Perlin::new(0x5EED),
+            scale,
+            octaves,
+            low,
+            high,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let n = self.noise.fbm(p * self.scale, self.octaves);
+        let t = (0.5 * (n + 1.0)).clamp(0.0, 1.0);
+        self.low * (1.0 - t) + self.high * t
+    }
+}
@@ -0,0 +1,209 @@
+use crate::image_io::linear_to_srgb;
+use crate::math::{luminance, Color};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMap {
+    /// Clip at 1
+    #[default]
+    None,
+    /// Luminance-based `L / (1 + L)`, keeps hues
+    Reinhard,
+    /// ACES RRT+ODT fit (Stephen Hill), filmic contrast and desaturated highlights
+    Aces,
+    /// AgX (Troy Sobotka), polynomial fit of the base look
+    Agx,
+}
+
+/// Output encoding applied after tone mapping.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Transfer {
+    /// Piecewise sRGB OETF
+    #[default]
+    Srgb,
+    /// Square root (gamma 2.0), the encoding of older renders
+    Gamma2,
+}
+
+impl ToneMap {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "none" | "clip" => Some(ToneMap::None),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" | "filmic" => Some(ToneMap::Aces),
+            "agx" => Some(ToneMap::Agx),
+            _ => None,
+        }
+    }
+}
+
+impl Transfer {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "srgb" => Some(Transfer::Srgb),
+            "gamma2" => Some(Transfer::Gamma2),
+            _ => None,
+        }
+    }
+}
+
+/// Scene-linear radiance -> display-encoded [0,1] values.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisplayTransform {
+    pub exposure: f64, // stops
+    pub tonemap: ToneMap,
+    pub transfer: Transfer,
+}
+
+impl DisplayTransform {
+    pub fn apply(&self, c: Color) -> Color {
+        let c = c * 2f64.powf(self.exposure);
+        let c = Color::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0));
+        let mapped = match self.tonemap {
+            ToneMap::None => c,
+            ToneMap::Reinhard => {
+                let l = luminance(c);
+                if l > 0.0 {
+                    c * (1.0 / (1.0 + l))
+                } else {
+                    c
+                }
+            }
+            ToneMap::Aces => aces(c),
+            ToneMap::Agx => agx(c),
+        };
+        let encode = |x: f64| {
+            let x = x.clamp(0.0, 1.0);
+            match self.transfer {
+                Transfer::Srgb => linear_to_srgb(x),
+                Transfer::Gamma2 => x.sqrt(),
+            }
+        };
+        Color::new(encode(mapped.x), encode(mapped.y), encode(mapped.z))
+    }
+}
+
+type Mat3 = [[f64; 3]; 3];
+
+fn mul(m: &Mat3, c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+        m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+        m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z,
+    )
+}
+
+// sRGB -> ACES AP1 (with the RRT saturation) and back, from Stephen Hill's fit
+const ACES_IN: Mat3 = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+const ACES_OUT: Mat3 = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn aces(c: Color) -> Color {
+    let v = mul(&ACES_IN, c);
+    let fit =
+        |x: f64| (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.432951) + 0.238081);
+    mul(&ACES_OUT, Color::new(fit(v.x), fit(v.y), fit(v.z)))
+}
+
+// AgX inset/outset matrices (row-major) and log2 exposure range
+const AGX_IN: Mat3 = [
+    [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+    [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+    [0.0423756549057051, 0.0784336, 0.879142973793104],
+];
+const AGX_OUT: Mat3 = [
+    [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+    [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+    [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+];
+const AGX_MIN_EV: f64 = -12.47393;
+const AGX_MAX_EV: f64 = 4.026069;
+
+fn agx(c: Color) -> Color {
+    let v = mul(&AGX_IN, c);
+    let curve = |x: f64| {
+        let x = (x.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV)
+            / (AGX_MAX_EV - AGX_MIN_EV);
+        // Sigmoid contrast curve, polynomial fit
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    };
+    let v = mul(&AGX_OUT, Color::new(curve(v.x), curve(v.y), curve(v.z)));
+    // The curve output is display-encoded with a 2.2 power; return to linear
+    let lin = |x: f64| x.max(0.0).powf(2.2);
+    Color::new(lin(v.x), lin(v.y), lin(v.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_io::srgb_to_linear;
+
+    #[test]
+    fn srgb_round_trips() {
+        for i in 0..=1000 {
+            let x = i as f64 / 1000.0;
+            assert!((srgb_to_linear(linear_to_srgb(x)) - x).abs() < 1e-12, "{x}");
+        }
+        // Every 8-bit level survives decoding and re-encoding
+        for v in 0..=255u8 {
+            let back = linear_to_srgb(srgb_to_linear(v as f64 / 255.0)) * 255.0;
+            assert_eq!(back.round() as u8, v);
+        }
+        assert!((linear_to_srgb(0.5) - 0.735357).abs() < 1e-6);
+        // The linear toe and the power curve meet
+        let knee = 0.0031308;
+        assert!((linear_to_srgb(knee - 1e-9) - linear_to_srgb(knee + 1e-9)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tone_maps_are_monotonic_and_stay_in_range() {
+        for tonemap in [
+            ToneMap::None,
+            ToneMap::Reinhard,
+            ToneMap::Aces,
+            ToneMap::Agx,
+        ] {
+            let display = DisplayTransform {
+                tonemap,
+                ..DisplayTransform::default()
+            };
+            let mut prev = -1.0;
+            for i in 0..=400 {
+                // Grays from 0 up to 2^8
+                let x = if i == 0 {
+                    0.0
+                } else {
+                    2f64.powf(i as f64 / 25.0 - 8.0)
+                };
+                let y = display.apply(Color::new(x, x, x));
+                assert!((0.0..=1.0).contains(&y.y), "{tonemap:?}({x}) = {}", y.y);
+                assert!(y.y >= prev, "{tonemap:?} decreases at {x}");
+                prev = y.y;
+            }
+            // Only clipping flattens out before the top of the range
+            let mid = display.apply(Color::new(2.0, 2.0, 2.0)).y;
+            let high = display.apply(Color::new(8.0, 8.0, 8.0)).y;
+            assert_eq!(mid == high, tonemap == ToneMap::None, "{tonemap:?}");
+        }
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let c = Color::new(0.05, 0.1, 0.2);
+        let up = DisplayTransform {
+            exposure: 2.0,
+            ..DisplayTransform::default()
+        };
+        let (a, b) = (up.apply(c), DisplayTransform::default().apply(c * 4.0));
+        assert!((a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::hittable::{HitRecord, Hittable, Shape};
+use crate::material::Material;
+use crate::math::{Point3, Vec3};
+use crate::ray::Ray;
+use crate::stats::{self, Prim};
+
+// Möller–Trumbore: returns (t, b1, b2) with b1/b2 the barycentrics of v1/v2
+pub fn intersect(
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let e1 = v1 - v0;
+    let e2 = v2 - v0;
+    let pvec = Vec3::cross(r.direction, e2);
+    let det = Vec3::dot(e1, pvec);
+    if det.abs() < 1e-12 {
+        return None; // ray parallel to the triangle
+    }
+    let inv_det = 1.0 / det;
+    let tvec = r.origin - v0;
+    let b1 = Vec3::dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec = Vec3::cross(tvec, e1);
+    let b2 = Vec3::dot(r.direction, qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = Vec3::dot(e2, qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+// dp/du and dp/dv of the affine map taking the vertex UVs to the vertices;
+// None if the UVs are collinear
+pub fn uv_tangents(v: [Point3; 3], uv: [(f64, f64); 3]) -> Option<(Vec3, Vec3)> {
+    let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+    let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let (e1, e2) = (v[1] - v[0], v[2] - v[0]);
+    Some(((e1 * dv2 - e2 * dv1) / det, (e2 * du1 - e1 * du2) / det))
+}
+
+// Uniform point on a triangle (Turk's square-root mapping)
+pub fn sample(v0: Point3, v1: Point3, v2: Point3, u: (f64, f64)) -> Point3 {
+    let su = u.0.sqrt();
+    let b0 = 1.0 - su;
+    let b1 = u.1 * su;
+    v0 * b0 + v1 * b1 + v2 * (1.0 - b0 - b1)
+}
+
+pub struct Triangle {
+    pub v0: Point3,
+    pub v1: Point3,
+    pub v2: Point3,
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Material) -> Self {
+        Self {
+            v0,
+            v1,
+            v2,
+            material,
+        }
+    }
+
+    // Counter-clockwise winding faces the viewer
+    fn outward_normal(&self) -> Vec3 {
+        Vec3::cross(self.v1 - self.v0, self.v2 - self.v0).unit()
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        stats::count_tests(Prim::Triangle, 1);
+        let (t, b1, b2) = intersect(self.v0, self.v1, self.v2, r, t_min, t_max)?;
+        // Barycentric UVs: v0 -> (0,0), v1 -> (1,0), v2 -> (0,1)
+        Some(
+            HitRecord::with_face_normal(r, r.at(t), self.outward_normal(), t, &self.material)
+                .with_uv(b1, b2, self.v1 - self.v0, self.v2 - self.v0),
+        )
+    }
+}
+
+impl Shape for Triangle {
+    fn area(&self) -> f64 {
+        0.5 * Vec3::cross(self.v1 - self.v0, self.v2 - self.v0).length()
+    }
+
+    fn sample_surface(&self, u: (f64, f64)) -> (Point3, Vec3) {
+        (sample(self.v0, self.v1, self.v2, u), self.outward_normal())
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::testing::{check_hit, point};
+    use crate::math::Color;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn uvs_are_the_barycentrics_of_the_hit(
+            v0 in point(2.0),
+            v1 in point(2.0),
+            v2 in point(2.0),
+            b1 in 0.01..0.49f64,
+            b2 in 0.01..0.49f64,
+            origin in point(10.0),
+        ) {
+            let tri = Triangle::new(v0, v1, v2, Material::new(Color::new(0.5, 0.5, 0.5), 0.0));
+            prop_assume!(tri.area() > 0.05);
+            let target = v0 * (1.0 - b1 - b2) + v1 * b1 + v2 * b2;
+            let r = Ray::new(origin, target - origin);
+            prop_assume!(Vec3::dot(r.direction.unit(), tri.outward_normal()).abs() > 0.05);
+            let rec = tri.hit(&r, 0.0, 2.0);
+            prop_assert!(rec.is_some(), "missed");
+            let rec = rec.unwrap();
+            check_hit(&rec, &r, 0.0, 2.0)?;
+            prop_assert!((rec.u - b1).abs() < 1e-6 && (rec.v - b2).abs() < 1e-6);
+            // The tangents rebuild the hit point from v0
+            let p = v0 + rec.dpdu * rec.u + rec.dpdv * rec.v;
+            prop_assert!((p - rec.p).length() < 1e-6, "{:?} != {:?}", p, rec.p);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Coordinator/worker tests: renders spread over workers on localhost must come
+// out byte-for-byte the same as a local render, also when workers fail.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+const SETTINGS: [&str; 5] = [
+    "--scene=all",
+    "--res=80x60",
+    "--spp=8",
+    "--sampler=sobol",
+    "--seed=1",
+];
+
+fn out_path(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("distributed");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+// The image, and what rt printed on stderr
+fn render(name: &str, flags: &[&str]) -> (Vec<u8>, String) {
+    let path = out_path(name);
+    let out = Command::new(env!("CARGO_BIN_EXE_rt"))
+        .args(SETTINGS)
+        .args(flags)
+        .arg(format!("--out={}", path.display()))
+        .output()
+        .expect("failed to run rt");
+    assert!(
+        out.status.success(),
+        "{name}: rt exited with {}",
+        out.status
+    );
+    let log = String::from_utf8_lossy(&out.stderr).into_owned();
+    (std::fs::read(path).unwrap(), log)
+}
+
+// An address nothing is listening on right now
+fn free_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+// `rt --worker`, killed when dropped
+struct Worker {
+    addr: String,
+    child: Child,
+}
+
+impl Worker {
+    fn start() -> Worker {
+        let addr = free_addr();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rt"))
+            .args(SETTINGS)
+            .arg(format!("--worker={addr}"))
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to run rt");
+        // Wait until it listens, then keep draining its log
+        let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+        for line in lines.by_ref() {
+            if line.unwrap().starts_with("worker: listening") {
+                break;
+            }
+        }
+        std::thread::spawn(move || lines.for_each(drop));
+        Worker { addr, child }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn read_frame(stream: &mut TcpStream) -> Vec<u8> {
+    let mut len = [0; 8];
+    stream.read_exact(&mut len).unwrap();
+    let mut buf = vec![0; u64::from_le_bytes(len) as usize];
+    stream.read_exact(&mut buf).unwrap();
+    buf
+}
+
+// Accepts one coordinator and its first tile, then hangs up mid-render
+fn start_failing_worker() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_frame(&mut stream);
+        let ok = 1u32.to_le_bytes();
+        stream.write_all(&(ok.len() as u64).to_le_bytes()).unwrap();
+        stream.write_all(&ok).unwrap();
+        assert_eq!(read_frame(&mut stream).len(), 16, "expected a tile");
+    });
+    addr
+}
+
+#[test]
+fn workers_match_a_local_render() {
+    let (local, _) = render("local.ppm", &[]);
+    let (a, b) = (Worker::start(), Worker::start());
+    let workers = format!("--workers={},{}", a.addr, b.addr);
+    let (distributed, log) = render("two_workers.ppm", &[&workers, "--tile-size=16"]);
+    assert!(local == distributed, "distributed render differs");
+    assert!(!log.contains("warning"), "{log}");
+}
+
+#[test]
+fn lost_workers_tiles_are_rendered_elsewhere() {
+    let (local, _) = render("local_for_lost.ppm", &[]);
+    let worker = Worker::start();
+    let workers = format!(
+        "--workers={},{},{}",
+        start_failing_worker(),
+        free_addr(),
+        worker.addr
+    );
+    let (distributed, log) = render("lost_workers.ppm", &[&workers, "--tile-size=16"]);
+    assert!(local == distributed, "distributed render differs");
+    // Both bad workers were noticed, and nothing fell back to local rendering
+    assert_eq!(log.matches("not using it").count(), 2, "{log}");
+    assert!(!log.contains("rendered locally"), "{log}");
+}
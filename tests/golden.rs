@@ -0,0 +1,164 @@
+// Golden-image tests: each scene is rendered small with fixed settings and
+// compared against tests/golden/<name>.ppm. On a mismatch the render and a
+// difference image are left in target/tmp/golden/ for inspection.
+//
+// After an intended change to the output, regenerate the references with
+//   UPDATE_GOLDEN=1 cargo test --test golden
+// and look at the new images before committing them.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SETTINGS: [&str; 4] = ["--res=80x60", "--spp=16", "--sampler=sobol", "--seed=1"];
+
+// Renders are deterministic, so the limits only leave room for floating-point
+// differences between platforms: RMSE over 8-bit channel values, and the share
+// of pixels allowed to change by more than CHANGED in some channel (which
+// catches bugs confined to a small part of the image).
+const MAX_RMSE: f64 = 0.5;
+const CHANGED: u8 = 16;
+const MAX_CHANGED_PIXELS: f64 = 0.005;
+
+struct Ppm {
+    width: usize,
+    height: usize,
+    data: Vec<u8>, // RGB, rows top to bottom
+}
+
+fn read_ppm(path: &Path) -> Ppm {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let mut tokens = text.split_whitespace();
+    assert_eq!(
+        tokens.next(),
+        Some("P3"),
+        "{}: not a P3 PPM",
+        path.display()
+    );
+    let mut num = || -> usize { tokens.next().and_then(|t| t.parse().ok()).unwrap() };
+    let (width, height, _max) = (num(), num(), num());
+    let data = (0..width * height * 3).map(|_| num() as u8).collect();
+    Ppm {
+        width,
+        height,
+        data,
+    }
+}
+
+fn write_ppm(path: &Path, width: usize, height: usize, data: &[u8]) {
+    let mut text = format!("P3\n{width} {height}\n255\n");
+    for px in data.chunks(3) {
+        text += &format!("{} {} {}\n", px[0], px[1], px[2]);
+    }
+    std::fs::write(path, text).unwrap();
+}
+
+fn rmse(a: &[u8], b: &[u8]) -> f64 {
+    let sum: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum();
+    (sum / a.len() as f64).sqrt()
+}
+
+fn changed_pixels(a: &[u8], b: &[u8]) -> f64 {
+    let changed = a
+        .chunks(3)
+        .zip(b.chunks(3))
+        .filter(|(p, q)| p.iter().zip(*q).any(|(&x, &y)| x.abs_diff(y) > CHANGED))
+        .count();
+    changed as f64 / (a.len() / 3) as f64
+}
+
+// The reference dimmed to gray, with differences in red (8x amplified)
+fn diff_image(expected: &[u8], actual: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(expected.len());
+    for (e, a) in expected.chunks(3).zip(actual.chunks(3)) {
+        let gray = (e[0] as u32 + e[1] as u32 + e[2] as u32) / 12;
+        let d = e.iter().zip(a).map(|(&x, &y)| x.abs_diff(y)).max().unwrap() as u32;
+        out.extend([(gray + 8 * d).min(255) as u8, gray as u8, gray as u8]);
+    }
+    out
+}
+
+fn check(name: &str, flags: &[&str]) {
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{name}.ppm"));
+    let status = Command::new(env!("CARGO_BIN_EXE_rt"))
+        .args(SETTINGS)
+        .args(flags)
+        .arg(format!("--out={}", actual_path.display()))
+        .stderr(std::process::Stdio::null())
+        .status()
+        .expect("failed to run rt");
+    assert!(status.success(), "{name}: rt exited with {status}");
+
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.ppm"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::copy(&actual_path, &golden_path).unwrap();
+        return;
+    }
+    let expected = read_ppm(&golden_path);
+    let actual = read_ppm(&actual_path);
+    assert_eq!(
+        (actual.width, actual.height),
+        (expected.width, expected.height),
+        "{name}: size changed"
+    );
+    let err = rmse(&expected.data, &actual.data);
+    let changed = changed_pixels(&expected.data, &actual.data);
+    if err > MAX_RMSE || changed > MAX_CHANGED_PIXELS {
+        let diff_path = out_dir.join(format!("{name}.diff.ppm"));
+        let diff = diff_image(&expected.data, &actual.data);
+        write_ppm(&diff_path, expected.width, expected.height, &diff);
+        panic!(
+            "{name}: differs from {}: RMSE {err:.3} (limit {MAX_RMSE}), {:.2}% of pixels changed \
+             (limit {:.2}%)\n  render: {}\n  diff:   {}",
+            golden_path.display(),
+            100.0 * changed,
+            100.0 * MAX_CHANGED_PIXELS,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn sphere() {
+    check("sphere", &["--scene=sphere"]);
+}
+
+#[test]
+fn cube_plane_dim() {
+    check("cube_plane_dim", &["--scene=cube_plane_dim"]);
+}
+
+#[test]
+fn all() {
+    check("all", &["--scene=all"]);
+}
+
+#[test]
+fn all_alt_cam() {
+    check("all_alt_cam", &["--scene=all_alt_cam"]);
+}
+
+#[test]
+fn custom() {
+    check(
+        "custom",
+        &[
+            // From above, so the cube top and cylinder cap are in view
+            "--lookfrom=0,1.2,0.4",
+            "--lookat=0,-0.3,-1.6",
+            "--add-plane=0,-0.5,0; 0,1,0; 0.8,0.8,0.8; 0",
+            "--add-sphere=-0.9,0,-1.6; 0.45; 0.9,0.3,0.2; 0.3",
+            "--add-cube=0,-0.15,-1.4; 0.6; 0.3,0.4,0.8; 0",
+            "--add-cylinder=0.9,-0.1,-1.6; 0.3; 0.4; 0.2,0.7,0.3; 0",
+            "--add-triangle=-0.6,0.4,-2.2; 0.6,0.4,-2.2; 0,1.1,-2.2; 0.9,0.8,0.2; 0",
+        ],
+    );
+}
@@ -0,0 +1,111 @@
+// Checkpoint/resume tests: a render killed after a checkpoint and resumed must
+// come out byte-for-byte the same as one that ran straight through.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const SETTINGS: [&str; 5] = [
+    "--scene=all",
+    "--res=64x48",
+    "--spp=128",
+    "--sampler=sobol",
+    "--seed=1",
+];
+
+fn out_path(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("resume");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn rt(flags: &[&str], out: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_rt"));
+    cmd.args(SETTINGS)
+        .args(flags)
+        .arg(format!("--out={}", out.display()));
+    cmd
+}
+
+fn render(name: &str, flags: &[&str]) -> Vec<u8> {
+    let path = out_path(name);
+    let out = rt(flags, &path).output().expect("failed to run rt");
+    assert!(
+        out.status.success(),
+        "{name}: rt exited with {}\n{}",
+        out.status,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    std::fs::read(path).unwrap()
+}
+
+// Starts a render that checkpoints as often as it can and kills it as soon as
+// the first checkpoint is on disk; returns the checkpoint's path
+fn interrupted(name: &str, flags: &[&str]) -> String {
+    let ckpt = out_path(&format!("{name}.ckpt"));
+    let _ = std::fs::remove_file(&ckpt);
+    let ckpt = ckpt.display().to_string();
+    let checkpoint = format!("--checkpoint={ckpt}");
+    let mut child = rt(
+        &[flags, &[&checkpoint, "--checkpoint-every=0"]].concat(),
+        &out_path(name),
+    )
+    .stderr(Stdio::null())
+    .spawn()
+    .expect("failed to run rt");
+    while !Path::new(&ckpt).exists() {
+        assert!(
+            child.try_wait().unwrap().is_none(),
+            "{name}: rt exited before writing a checkpoint"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let running = child.try_wait().unwrap().is_none();
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        running,
+        "{name}: rt finished before it could be interrupted"
+    );
+    ckpt
+}
+
+fn resumed(name: &str, ckpt: &str, flags: &[&str]) -> Vec<u8> {
+    let checkpoint = format!("--checkpoint={ckpt}");
+    render(name, &[flags, &[&checkpoint, "--resume"]].concat())
+}
+
+#[test]
+fn resumed_renders_match_uninterrupted_ones() {
+    let straight = render("straight.ppm", &[]);
+    let ckpt = interrupted("killed.ppm", &[]);
+    assert!(
+        resumed("resumed.ppm", &ckpt, &[]) == straight,
+        "resumed render differs"
+    );
+}
+
+#[test]
+fn resumed_progressive_renders_match_uninterrupted_ones() {
+    let straight = render("straight_progressive.ppm", &["--progressive"]);
+    let ckpt = interrupted("killed_progressive.ppm", &["--progressive"]);
+    let resumed = resumed("resumed_progressive.ppm", &ckpt, &["--progressive"]);
+    assert!(resumed == straight, "resumed progressive render differs");
+}
+
+#[test]
+fn progressive_resume_finishes_a_row_by_row_checkpoint() {
+    // The checkpoint has finished rows and untouched ones. Samples land on
+    // the film in a different order, so allow for rounding.
+    let straight = render("straight_mixed.ppm", &[]);
+    let ckpt = interrupted("killed_mixed.ppm", &[]);
+    let resumed = resumed("resumed_mixed.ppm", &ckpt, &["--progressive"]);
+    assert_eq!(resumed.len(), straight.len());
+    let worst = resumed
+        .iter()
+        .zip(&straight)
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap();
+    assert!(worst <= 1, "resumed render is off by up to {worst} levels");
+}